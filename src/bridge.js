@@ -0,0 +1,91 @@
+window.seal = {
+  // posts `value` to Luau as JSON; decoded to Luau values when ipc_mode = "json"
+  post: (value) => window.ipc.postMessage(JSON.stringify(value)),
+  // set to a function to receive payloads sent with WebviewIpc:send
+  onmessage: null,
+  // calls the Luau function bound with WebviewIpc:bind; resolves with its return value
+  invoke: (name, ...args) => new Promise((resolve, reject) => {
+    const id = window.seal.__next_call++;
+    window.seal.__calls.set(id, { resolve, reject });
+    // not keepalive, since browsers cap those bodies at 64 KiB and args can be bigger than that
+    window.seal.__post_control("invoke", { id, name, args }, false)
+      .catch((err) => window.seal.__settle(id, false, `seal.invoke could not reach webseal: ${err.message}`));
+  }),
+  // moves, sizes and closes the window, for pages that draw their own titlebar (decorations = "none");
+  // webseal's custom titlebar uses these too
+  window: {
+    // starts moving the window with the mouse; call it from a mousedown handler
+    drag: () => window.seal.__control("drag_window"),
+    minimize: () => window.seal.__control("minimize"),
+    toggle_maximize: () => window.seal.__control("maximize"),
+    close: () => window.seal.__control("close"),
+    // lets the edges of the page resize the window; returns a function that turns it back off
+    enable_resize: () => {
+      // a little wider than the 5px webseal resizes from, so zoomed pages still reach it
+      const edge = 8;
+      const near_edge = (e) => e.clientX < edge || e.clientY < edge
+        || e.clientX >= window.innerWidth - edge || e.clientY >= window.innerHeight - edge;
+      // only moves near the edges matter, plus the first one away from them to put the cursor back,
+      // and those are sent at most once a frame
+      let was_near = false;
+      let pending = null;
+      const move = (e) => {
+        const near = near_edge(e);
+        if (!near && !was_near) return;
+        was_near = near;
+        if (pending === null) {
+          requestAnimationFrame(() => {
+            window.seal.__control("mouse_move", pending);
+            pending = null;
+          });
+        }
+        pending = { x: e.clientX, y: e.clientY };
+      };
+      const down = (e) => {
+        if (e.button === 0 && near_edge(e)) window.seal.__control("mouse_down", { x: e.clientX, y: e.clientY });
+      };
+      document.addEventListener("mousemove", move);
+      document.addEventListener("mousedown", down);
+      return () => {
+        document.removeEventListener("mousemove", move);
+        document.removeEventListener("mousedown", down);
+      };
+    },
+  },
+  // talks to webseal itself (titlebar, invoke) over seal:// instead of window.ipc, so it never
+  // gets mixed up with messages for Luau; see control.rs for the message format
+  __control: (kind, fields) => {
+    window.seal.__post_control(kind, fields, true)
+      .catch((err) => console.error("seal: control message failed", err));
+  },
+  // rejects if the message couldn't be sent or webseal turned it down
+  __post_control: async (kind, fields, keepalive) => {
+    const response = await fetch("!REPLACECONTROLURL!", {
+      method: "POST",
+      // lets pages from other origins send these too, though they only get an opaque response back
+      mode: "no-cors",
+      keepalive,
+      body: JSON.stringify({ v: 1, kind, ...fields }),
+    });
+    // pages from other origins only see an opaque response, so for them only a failed fetch shows up
+    if (response.type !== "opaque" && !response.ok) {
+      throw new Error(`webseal answered ${response.status}`);
+    }
+  },
+  // called by webseal; also dispatches a "seal:message" CustomEvent on window
+  __receive: (payload) => {
+    window.dispatchEvent(new CustomEvent("seal:message", { detail: payload }));
+    if (typeof window.seal.onmessage === "function") {
+      window.seal.onmessage(payload);
+    }
+  },
+  // called by webseal with the result of a seal.invoke call
+  __settle: (id, ok, value) => {
+    const call = window.seal.__calls.get(id);
+    if (!call) return;
+    window.seal.__calls.delete(id);
+    ok ? call.resolve(value) : call.reject(new Error(value));
+  },
+  __calls: new Map(),
+  __next_call: 0,
+};
@@ -0,0 +1,64 @@
+use tao::{
+    dpi::{LogicalSize, PhysicalPosition},
+    window::{Fullscreen, Window},
+};
+
+/// Changes to the window that Luau can make at runtime with setters like `WebviewIpc:set_title()`.
+///
+/// Applied on the UI thread, see `ToWindow::Command`.
+#[derive(Debug, Clone)]
+pub enum WindowCommand {
+    SetTitle(String),
+    /// Inner size in logical pixels, like the `size` option.
+    SetSize(f32, f32),
+    /// Outer position in physical pixels, like `WebviewIpc:position()` returns.
+    SetPosition(f32, f32),
+    /// Centers the window on its current monitor.
+    Center,
+    SetMinSize(Option<(f32, f32)>),
+    SetMaxSize(Option<(f32, f32)>),
+    SetResizable(bool),
+    SetAlwaysOnTop(bool),
+    SetFullscreen(bool),
+    SetVisible(bool),
+    Focus,
+    Minimize,
+    Maximize,
+    Unmaximize,
+}
+
+impl WindowCommand {
+    pub fn apply(self, window: &Window) {
+        match self {
+            WindowCommand::SetTitle(title) => window.set_title(&title),
+            WindowCommand::SetSize(width, height) => window.set_inner_size(LogicalSize::new(width, height)),
+            WindowCommand::SetPosition(x, y) => window.set_outer_position(PhysicalPosition::new(x, y)),
+            WindowCommand::Center => {
+                if let Some(monitor) = window.current_monitor() {
+                    let monitor_position = monitor.position();
+                    let monitor_size = monitor.size();
+                    let window_size = window.outer_size();
+                    let x = monitor_position.x + (monitor_size.width as i32 - window_size.width as i32) / 2;
+                    let y = monitor_position.y + (monitor_size.height as i32 - window_size.height as i32) / 2;
+                    window.set_outer_position(PhysicalPosition::new(x, y));
+                }
+            },
+            WindowCommand::SetMinSize(size) => {
+                window.set_min_inner_size(size.map(|(width, height)| LogicalSize::new(width, height)))
+            },
+            WindowCommand::SetMaxSize(size) => {
+                window.set_max_inner_size(size.map(|(width, height)| LogicalSize::new(width, height)))
+            },
+            WindowCommand::SetResizable(resizable) => window.set_resizable(resizable),
+            WindowCommand::SetAlwaysOnTop(always_on_top) => window.set_always_on_top(always_on_top),
+            WindowCommand::SetFullscreen(fullscreen) => {
+                window.set_fullscreen(fullscreen.then_some(Fullscreen::Borderless(None)))
+            },
+            WindowCommand::SetVisible(visible) => window.set_visible(visible),
+            WindowCommand::Focus => window.set_focus(),
+            WindowCommand::Minimize => window.set_minimized(true),
+            WindowCommand::Maximize => window.set_maximized(true),
+            WindowCommand::Unmaximize => window.set_maximized(false),
+        }
+    }
+}
@@ -0,0 +1,174 @@
+use std::borrow::Cow;
+
+use serde_json::Value;
+use wry::http::{Method, Request, Response, StatusCode};
+
+use crate::protocol;
+use crate::window::UserEvent;
+
+/// Path on the `seal://app/` origin that `seal.__control` in bridge.js posts to. Control messages
+/// never go through `window.ipc`, so nothing a page posts there is mistaken for one.
+///
+/// The body is a JSON object like `{ "v": 1, "kind": "mouse_down", "x": 10, "y": 20 }`.
+pub const CONTROL_PATH: &str = "__seal/control";
+
+/// Bumped whenever the shape of control messages changes, so a stale page can't send something we'd misread.
+pub const CONTROL_VERSION: u64 = 1;
+
+/// Parses `request` if it's a control message.
+///
+/// Returns None for every other request, and Some(Err) with a reason for control messages that are malformed.
+pub fn parse_request(request: &Request<Vec<u8>>) -> Option<Result<UserEvent, String>> {
+    if request.method() != Method::POST || request.uri().path().trim_start_matches('/') != CONTROL_PATH {
+        return None;
+    }
+    match std::str::from_utf8(request.body()) {
+        Ok(body) => Some(parse_message(body)),
+        Err(_) => Some(Err(String::from("not valid UTF-8"))),
+    }
+}
+
+/// Answers `request` if it's a control message, handing the event in it to `send`.
+///
+/// Returns None for every other request, so `SealProtocol` can serve it instead.
+pub fn handle_request(request: &Request<Vec<u8>>, send: impl FnOnce(UserEvent)) -> Option<Response<Cow<'static, [u8]>>> {
+    match parse_request(request)? {
+        Ok(event) => {
+            send(event);
+            Some(protocol::text_response(StatusCode::NO_CONTENT, ""))
+        },
+        // a page posting garbage shouldn't take the window down with it
+        Err(err) => {
+            eprintln!("ignoring malformed control message: {}", err);
+            Some(protocol::text_response(StatusCode::BAD_REQUEST, "malformed control message"))
+        },
+    }
+}
+
+fn parse_message(message: &str) -> Result<UserEvent, String> {
+    let message: Value = match serde_json::from_str(message) {
+        Ok(message) => message,
+        Err(err) => return Err(format!("not valid JSON: {}", err)),
+    };
+
+    match message["v"].as_u64() {
+        Some(CONTROL_VERSION) => {},
+        Some(version) => return Err(format!("unsupported version {} (expected {})", version, CONTROL_VERSION)),
+        None => return Err(String::from("missing version")),
+    }
+
+    let Some(kind) = message["kind"].as_str() else {
+        return Err(String::from("missing kind"));
+    };
+
+    match kind {
+        "minimize" => Ok(UserEvent::Minimize),
+        "maximize" => Ok(UserEvent::Maximize),
+        "drag_window" => Ok(UserEvent::DragWindow),
+        "close" => Ok(UserEvent::CloseWindow),
+        "mouse_down" => {
+            let (x, y) = point(&message, kind)?;
+            Ok(UserEvent::MouseDown(x, y))
+        },
+        "mouse_move" => {
+            let (x, y) = point(&message, kind)?;
+            Ok(UserEvent::MouseMove(x, y))
+        },
+        "invoke" => match (message["id"].as_u64(), message["name"].as_str()) {
+            (Some(id), Some(name)) => {
+                // seal.invoke always sends args as an array, but default to none rather than reject
+                let args = match &message["args"] {
+                    Value::Null => Value::Array(Vec::new()),
+                    args => args.clone(),
+                };
+                Ok(UserEvent::Invoke(id, name.to_string(), args))
+            },
+            _ => Err(String::from("invoke without id or name")),
+        },
+        other => Err(format!("unknown kind '{}'", other)),
+    }
+}
+
+/// Gets the `x` and `y` of a pointer event in CSS pixels, which can be fractional when the page is zoomed.
+fn point(message: &Value, kind: &str) -> Result<(i32, i32), String> {
+    match (message["x"].as_f64(), message["y"].as_f64()) {
+        (Some(x), Some(y)) if x.is_finite() && y.is_finite() => Ok((x as i32, y as i32)),
+        _ => Err(format!("{} without a numeric x and y", kind)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn control_request(method: Method, path: &str, body: &str) -> Request<Vec<u8>> {
+        Request::builder()
+            .method(method)
+            .uri(format!("seal://app/{}", path))
+            .body(body.as_bytes().to_vec())
+            .unwrap()
+    }
+
+    #[test]
+    fn parses_kinds() {
+        assert!(matches!(parse_message(r#"{"v":1,"kind":"minimize"}"#), Ok(UserEvent::Minimize)));
+        assert!(matches!(parse_message(r#"{"v":1,"kind":"close"}"#), Ok(UserEvent::CloseWindow)));
+        assert!(matches!(parse_message(r#"{"v":1,"kind":"mouse_down","x":10.7,"y":-2}"#), Ok(UserEvent::MouseDown(10, -2))));
+    }
+
+    #[test]
+    fn invoke_defaults_to_no_args() {
+        match parse_message(r#"{"v":1,"kind":"invoke","id":3,"name":"add"}"#) {
+            Ok(UserEvent::Invoke(3, name, Value::Array(args))) => {
+                assert_eq!(name, "add");
+                assert!(args.is_empty());
+            },
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_messages() {
+        assert!(parse_message("not json").is_err());
+        assert!(parse_message(r#"{"kind":"minimize"}"#).is_err());
+        assert!(parse_message(r#"{"v":2,"kind":"minimize"}"#).is_err());
+        assert!(parse_message(r#"{"v":1}"#).is_err());
+        assert!(parse_message(r#"{"v":1,"kind":"explode"}"#).is_err());
+        assert!(parse_message(r#"{"v":1,"kind":"mouse_move","x":"1","y":2}"#).is_err());
+        assert!(parse_message(r#"{"v":1,"kind":"invoke","name":"add"}"#).is_err());
+    }
+
+    #[test]
+    fn only_posts_to_the_control_path_are_control_messages() {
+        let body = r#"{"v":1,"kind":"maximize"}"#;
+        assert!(matches!(parse_request(&control_request(Method::POST, CONTROL_PATH, body)), Some(Ok(UserEvent::Maximize))));
+        assert!(parse_request(&control_request(Method::GET, CONTROL_PATH, body)).is_none());
+        assert!(parse_request(&control_request(Method::POST, "index.html", body)).is_none());
+        assert!(matches!(parse_request(&control_request(Method::POST, CONTROL_PATH, "garbage")), Some(Err(_))));
+    }
+
+    #[test]
+    fn handles_control_requests_with_a_body() {
+        // what bridge.js sends for a mousedown on the left edge
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri(protocol::app_url(CONTROL_PATH))
+            .header("content-type", "text/plain;charset=UTF-8")
+            .body(br#"{"v":1,"kind":"mouse_down","x":2,"y":120}"#.to_vec())
+            .unwrap();
+        let mut sent = None;
+        let response = handle_request(&request, |event| sent = Some(event)).unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert!(matches!(sent, Some(UserEvent::MouseDown(2, 120))));
+
+        // webkitgtk drops the body without wry's linux-body feature, which would end up here
+        let (mut parts, _) = request.into_parts();
+        parts.headers.clear();
+        let empty = Request::from_parts(parts, Vec::new());
+        let response = handle_request(&empty, |_| panic!("sent an event for an empty body")).unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let page = control_request(Method::GET, "index.html", "");
+        assert!(handle_request(&page, |_| panic!("sent an event for a page request")).is_none());
+    }
+}
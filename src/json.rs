@@ -0,0 +1,187 @@
+use std::ffi::c_int;
+
+use bstr::ByteSlice;
+use seal::ffi;
+use serde_json::{Map, Number, Value};
+
+use crate::utils;
+
+/// Tables nested deeper than this are assumed to be cyclic.
+const MAX_DEPTH: usize = 128;
+
+/// Pushes the JSON `null` sentinel onto the Luau stack.
+///
+/// JSON null can't be represented by Luau `nil` (it would disappear from tables),
+/// so we use a null lightuserdata instead. All null lightuserdatas compare equal in Luau,
+/// so `value == webseal.null` works.
+///
+/// # Safety
+/// - state must be a non-null pointer to a lua_State
+pub unsafe fn push_null(state: *mut ffi::lua_State) {
+    unsafe { ffi::lua_pushlightuserdata(state, std::ptr::null_mut()) };
+}
+
+/// Pushes `value` onto the Luau stack as its Luau equivalent:
+/// - objects become tables with string keys, arrays become 1-indexed tables
+/// - numbers become Luau numbers (f64), strings stay strings (may contain NUL bytes)
+/// - null becomes the null sentinel (see `push_null`)
+///
+/// After this returns, the stack top is the converted value.
+///
+/// # Safety
+/// - state must be a non-null pointer to a lua_State
+pub unsafe fn push_value(state: *mut ffi::lua_State, value: &Value) {
+    unsafe {
+        // nested tables need 3 slots: the table, a key and a value
+        ffi::luaL_checkstack(state, 3, c"json value nested too deeply".as_ptr());
+        match value {
+            Value::Null => push_null(state),
+            Value::Bool(b) => ffi::lua_pushboolean(state, *b as c_int),
+            Value::Number(n) => ffi::lua_pushnumber(state, n.as_f64().unwrap_or(f64::NAN)),
+            Value::String(s) => ffi::lua_pushlstring(state, s.as_ptr() as *const _, s.len()),
+            Value::Array(values) => {
+                ffi::lua_createtable(state, values.len() as c_int, 0);
+                for (i, value) in values.iter().enumerate() {
+                    push_value(state, value);
+                    // luau arrays start at 1
+                    ffi::lua_rawseti(state, -2, (i + 1) as c_int);
+                }
+            },
+            Value::Object(map) => {
+                ffi::lua_createtable(state, 0, map.len() as c_int);
+                for (key, value) in map {
+                    ffi::lua_pushlstring(state, key.as_ptr() as *const _, key.len());
+                    push_value(state, value);
+                    ffi::lua_rawset(state, -3);
+                }
+            }
+        }
+    }
+}
+
+/// Converts the Luau value at `idx` into JSON without popping it.
+/// - tables with only the keys `1..n` become arrays, empty tables become empty arrays
+/// - other tables become objects; their keys must be strings or numbers
+/// - the null sentinel (see `push_null`) becomes null
+///
+/// Returns an error message if the value (or anything nested in it) can't be represented as JSON.
+///
+/// # Safety
+/// - state must be a non-null pointer to a lua_State
+/// - `idx` must be on the luau stack and must not be a pseudo-index
+pub unsafe fn value_from_stack(state: *mut ffi::lua_State, idx: c_int) -> Result<Value, String> {
+    let idx = if idx < 0 { unsafe { ffi::lua_gettop(state) + idx + 1 } } else { idx };
+    unsafe { convert(state, idx, 0) }
+}
+
+/// # Safety
+/// - `idx` must be an absolute index on the luau stack
+unsafe fn convert(state: *mut ffi::lua_State, idx: c_int, depth: usize) -> Result<Value, String> {
+    let t = unsafe { ffi::lua_type(state, idx) };
+    if t == ffi::LUA_TNIL {
+        Ok(Value::Null)
+    } else if t == ffi::LUA_TBOOLEAN {
+        Ok(Value::Bool(unsafe { ffi::lua_toboolean(state, idx) } != 0))
+    } else if t == ffi::LUA_TNUMBER {
+        number(unsafe { ffi::lua_tonumber(state, idx) })
+    } else if t == ffi::LUA_TSTRING {
+        Ok(Value::String(unsafe { string_at(state, idx) }))
+    } else if t == ffi::LUA_TLIGHTUSERDATA && unsafe { ffi::lua_tolightuserdata(state, idx) }.is_null() {
+        Ok(Value::Null)
+    } else if t == ffi::LUA_TTABLE {
+        if depth >= MAX_DEPTH {
+            return Err(String::from("table is nested too deeply (is it cyclic?)"));
+        }
+        unsafe { table(state, idx, depth) }
+    } else {
+        Err(format!("can't convert {} to JSON", unsafe { utils::type_of(state, idx) }))
+    }
+}
+
+fn number(n: f64) -> Result<Value, String> {
+    // keep integers looking like integers on the JS side (1, not 1.0)
+    if n.fract() == 0.0 && n.abs() < 9007199254740992.0 {
+        Ok(Value::from(n as i64))
+    } else {
+        Number::from_f64(n)
+            .map(Value::Number)
+            .ok_or_else(|| format!("can't convert {} to JSON", n))
+    }
+}
+
+/// # Safety
+/// - the value at `idx` must be a string
+unsafe fn string_at(state: *mut ffi::lua_State, idx: c_int) -> String {
+    let mut len = 0;
+    let ptr = unsafe { ffi::lua_tolstring(state, idx, &mut len) };
+    // SAFETY: luau strings are valid for len bytes while they're on the stack
+    let bytes = unsafe { std::slice::from_raw_parts(ptr as *const u8, len) };
+    bytes.to_str_lossy().to_string()
+}
+
+enum Key {
+    Index(f64),
+    Name(String),
+}
+
+/// # Safety
+/// - the value at `idx` must be a table, `idx` must be absolute
+unsafe fn table(state: *mut ffi::lua_State, idx: c_int, depth: usize) -> Result<Value, String> {
+    let mut entries: Vec<(Key, Value)> = Vec::new();
+    unsafe {
+        // lua_next needs the key and value on the stack, plus room for nested conversions
+        ffi::luaL_checkstack(state, 3, c"table nested too deeply".as_ptr());
+        ffi::lua_pushnil(state);
+        while ffi::lua_next(state, idx) != 0 {
+            // stack: [ ..., key, value ]
+            // don't lua_tostring number keys, that converts them in place and breaks lua_next
+            let key_type = ffi::lua_type(state, -2);
+            let key = if key_type == ffi::LUA_TNUMBER {
+                Key::Index(ffi::lua_tonumber(state, -2))
+            } else if key_type == ffi::LUA_TSTRING {
+                Key::Name(string_at(state, -2))
+            } else {
+                let got_t = utils::type_of(state, -2);
+                // pop key and value to balance stack
+                ffi::lua_pop(state, 2);
+                return Err(format!("can't convert table with {} keys to JSON", got_t));
+            };
+            let value = match convert(state, ffi::lua_gettop(state), depth + 1) {
+                Ok(value) => value,
+                Err(err) => {
+                    ffi::lua_pop(state, 2);
+                    return Err(err);
+                }
+            };
+            entries.push((key, value));
+            // pop value, keep key for lua_next
+            ffi::lua_pop(state, 1);
+        }
+    }
+
+    let len = entries.len();
+    let is_array = entries.iter().all(|(key, _)| {
+        matches!(key, Key::Index(i) if i.fract() == 0.0 && *i >= 1.0 && *i <= len as f64)
+    });
+
+    if is_array {
+        let mut values = vec![Value::Null; len];
+        for (key, value) in entries {
+            if let Key::Index(i) = key {
+                values[i as usize - 1] = value;
+            }
+        }
+        Ok(Value::Array(values))
+    } else {
+        let mut map = Map::with_capacity(len);
+        for (key, value) in entries {
+            let key = match key {
+                Key::Name(name) => name,
+                Key::Index(i) if i.fract() == 0.0 => (i as i64).to_string(),
+                Key::Index(i) => i.to_string(),
+            };
+            map.insert(key, value);
+        }
+        Ok(Value::Object(map))
+    }
+}
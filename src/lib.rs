@@ -1,5 +1,3 @@
-use std::cell::RefCell;
-
 use std::ffi::c_int;
 
 pub mod utils;
@@ -7,6 +5,14 @@ pub mod options;
 
 mod webview_ipc;
 mod resize;
+mod json;
+mod query;
+mod command;
+mod control;
+mod protocol;
+mod luau_protocol;
+mod ui;
+mod window;
 
 use webview_ipc::WebviewIpc;
 
@@ -14,225 +20,53 @@ use seal::{ffi, push_wrapped_c_function, push_wrapped_error};
 
 use options::WebviewOptions;
 
-use tao::{
-    dpi::LogicalSize, event::{Event, StartCause, WindowEvent}, 
-    event_loop::{ControlFlow, EventLoopBuilder}, 
-    platform::unix::EventLoopBuilderExtUnix, 
-    window::{UserAttentionType, WindowBuilder}
-};
-use wry::{WebViewBuilder, http::Request};
-use tao::platform::unix::WindowExtUnix;
-
-use crate::resize::HitTestResult;
-
-enum UserEvent {
-    Minimize,
-    Maximize,
-    DragWindow,
-    CloseWindow,
-    MouseDown(i32, i32),
-    MouseMove(i32, i32),
-    SendIpc(String),
-}
-
 #[derive(Debug)]
 pub enum ToLuau {
     IpcMessage(String),
-    SizeReturned(f32, f32),
+    JsonMessage(serde_json::Value),
+    /// Call from `seal.invoke(name, ...args)` in the page; answered with `ToWindow::InvokeResult` with the same id.
+    Invoke(u64, String, serde_json::Value),
+    /// Request to the scheme's Luau protocol handler; answered with `ToWindow::ProtocolResponse` with the same id.
+    ProtocolRequest(u64, String, Box<wry::http::Request<Vec<u8>>>),
     WindowClosed,
+    // native window events, only sent if enabled in WebviewOptions::events
+    Resized(f32, f32),
+    Moved(f32, f32),
+    Focused(bool),
+    /// New scale factor and new inner size.
+    ScaleFactorChanged(f64, f32, f32),
+    ThemeChanged(&'static str),
+    FileDropped(std::path::PathBuf),
+    FileHovered(std::path::PathBuf),
+    FileHoverCancelled,
 }
 
 #[derive(Debug)]
 pub enum ToWindow {
     ReplaceHtml(String),
+    Navigate(String),
+    /// Serves the asset at the path over `seal://app/`.
+    RegisterAsset(String, protocol::Asset),
+    EvaluateScript(String),
+    /// Runs the script before the page's own scripts in every page loaded after this.
+    AddInitScript(String),
+    /// Evaluates the script and replies with its JSON-serialized result.
+    EvaluateScriptWithResult(String, crossbeam_channel::Sender<String>),
+    /// JSON-serialized payload to dispatch to the page with `window.seal.__receive`.
+    SendMessage(String),
+    /// Settles the `seal.invoke` promise with the same id; Ok holds the JSON-serialized return value,
+    /// Err holds the error message to reject with.
+    InvokeResult(u64, Result<String, String>),
+    ProtocolResponse(u64, Box<wry::http::Response<Vec<u8>>>),
     SetAlert(bool),
-    SizeRequested,
+    /// Replies with the inner size of the window in physical pixels.
+    SizeRequested(crossbeam_channel::Sender<(f32, f32)>),
+    /// Replies with the answer to a query about window state.
+    Query(query::WindowQuery, crossbeam_channel::Sender<query::QueryReply>),
+    Command(command::WindowCommand),
     Close,
-}
-
-fn spawn(options: WebviewOptions, sender: crossbeam_channel::Sender<ToLuau>, receiver: crossbeam_channel::Receiver<ToWindow>) -> wry::Result<()> {
-    let event_loop = EventLoopBuilder::<UserEvent>
-        ::with_user_event()
-        .with_any_thread(true)
-        .build();
-
-    let builder = WindowBuilder::new()
-        .with_decorations(false)
-        .with_transparent(true)
-        .with_title(&options.title)
-        .with_inner_size(LogicalSize::new(options.size.0, options.size.1))
-        .with_resizable(options.resizeable);
-
-    // why do none of these methods take in &self instead of the whole self
-    let builder = if let Some(max_size) = options.max_size {
-        builder.with_max_inner_size(LogicalSize::new(max_size.0, max_size.1))
-    } else { 
-        builder 
-    };
-
-    let builder = if let Some(min_size) = options.min_size {
-        builder.with_min_inner_size(LogicalSize::new(min_size.0, min_size.1))
-    } else {
-        builder
-    };
-
-    let window = builder
-        .build(&event_loop)
-        .unwrap();
-
-    let handler_proxy = event_loop.create_proxy();
-    let handler = move |req: Request<String>| {
-        let body = req.body();
-        if body.starts_with("input!") {
-            let body = body.replace("input!", "");
-            let mut req = body.split([':', ',']);
-            match req.next().unwrap() {
-                "minimize" => {
-                    let _ = handler_proxy.send_event(UserEvent::Minimize);
-                }
-                "maximize" => {
-                    let _ = handler_proxy.send_event(UserEvent::Maximize);
-                }
-                "drag_window" => {
-                    let _ = handler_proxy.send_event(UserEvent::DragWindow);
-                }
-                "close" => {
-                    let _ = handler_proxy.send_event(UserEvent::CloseWindow);
-                }
-                "mousedown" => {
-                    let x = req.next().unwrap().parse().unwrap();
-                    let y = req.next().unwrap().parse().unwrap();
-                    let _ = handler_proxy.send_event(UserEvent::MouseDown(x, y));
-                }
-                "mousemove" => {
-                    let x = req.next().unwrap().parse().unwrap();
-                    let y = req.next().unwrap().parse().unwrap();
-                    let _ = handler_proxy.send_event(UserEvent::MouseMove(x, y));
-                }
-                _ => {}
-            }
-        } else {
-            let _ = handler_proxy.send_event(UserEvent::SendIpc(body.clone()));
-            // let _ = handler_proxy.send_event(UserEvent::InvokeLuauCallback(body.clone()));
-        }
-    };
-
-    const HTML_HEADER: &str = include_str!("./template.html");
-
-    let html = HTML_HEADER.replace("!REPLACETITLE!", &options.title);
-    let html = html.replace("!REPLACEBODY!", &options.html);
-
-    let builder = WebViewBuilder::new()
-        .with_html(html)
-        .with_transparent(true)
-        .with_ipc_handler(handler)
-        .with_accept_first_mouse(true);
-
-    #[cfg(any(
-        target_os = "windows",
-        target_os = "macos",
-        target_os = "ios",
-        target_os = "android"
-    ))]
-    let webview = builder.build(&window)?;
-    #[cfg(not(any(
-        target_os = "windows",
-        target_os = "macos",
-        target_os = "ios",
-        target_os = "android"
-    )))]
-    let webview = {
-        use wry::WebViewBuilderExtUnix;
-        let vbox = window.default_vbox().unwrap();
-        builder.build_gtk(vbox)?
-    };
-
-    // let mut webview = Some(webview);
-    let webview = RefCell::new(webview);
-    event_loop.run(move |event, _, control_flow| {
-        *control_flow = ControlFlow::Poll;
-
-        let mut new_html: Option<String> = None;
-        let mut set_alert: Option<bool> = None;
-        match receiver.try_recv() {
-            Ok(ToWindow::ReplaceHtml(html)) => {
-                new_html = Some(html);
-            },
-            Ok(ToWindow::SetAlert(enabled)) => {
-                set_alert = Some(enabled);
-            },
-            Ok(ToWindow::Close) => {
-                *control_flow = ControlFlow::Exit;
-            },
-            Ok(ToWindow::SizeRequested) => {
-                let size = window.inner_size();
-                let width = size.width as f32;
-                let height = size.height as f32;
-                if let Err(err) = sender.send(ToLuau::SizeReturned(width, height)) {
-                    eprintln!("error reporting size to luau: {}", err);
-                }
-            }
-            _ => {}
-        }
-
-        if let Some(new_html) = new_html {
-            let webview = webview.borrow_mut();
-            let html = HTML_HEADER.replace("!REPLACETITLE!", &options.title);
-            let html = html.replace("!REPLACEBODY!", &new_html);
-            let _ = webview.load_html(&html);
-        }
-
-        if let Some(should_set_alert) = set_alert {
-            // let webview = webview.borrow_mut();
-            if should_set_alert {
-                window.request_user_attention(Some(UserAttentionType::Critical));
-            } else {
-                window.request_user_attention(None);
-            }
-        }
-
-        match event {
-            Event::NewEvents(StartCause::Init) => {},
-            Event::WindowEvent {
-                event: WindowEvent::CloseRequested,
-                ..
-            }
-            | Event::UserEvent(UserEvent::CloseWindow) => {
-                if let Err(err) = sender.send(ToLuau::WindowClosed) {
-                    eprintln!("can't tell luau we closed window due to err: {}", err);
-                }
-                let _ = webview.borrow_mut();
-                *control_flow = ControlFlow::Exit
-            }
-            Event::UserEvent(e) => match e {
-                UserEvent::Minimize => window.set_minimized(true),
-                UserEvent::Maximize => window.set_maximized(!window.is_maximized()),
-                UserEvent::DragWindow => window.drag_window().unwrap(),
-                UserEvent::MouseDown(x, y) => {
-                    let res = resize::check_bounds(window.inner_size(), x, y, window.scale_factor());
-                    match res {
-                        HitTestResult::Client | HitTestResult::NoWhere => {}
-                        _ => res.drag_resize_window(&window),
-                    }
-                }
-                UserEvent::MouseMove(x, y) => {
-                    resize::check_bounds(window.inner_size(), x, y, window.scale_factor())
-                        .change_cursor(&window);
-                }
-                UserEvent::CloseWindow => { /* handled above */ },
-                UserEvent::SendIpc(body) => {
-                    if let Err(err) = sender.send(ToLuau::IpcMessage(body)) {
-                        eprintln!("unable to send ipc message due to err: {}", err);
-                    }
-                }
-            },
-            _ => (),
-        }
-
-        // prevent busy waiting due to controlflow Poll
-        // std::thread::sleep(std::time::Duration::from_millis(20));
-    });
-    // Ok(())
+    /// Closes the window without telling Luau, for handles that were destroyed or garbage collected.
+    Destroy,
 }
 
 unsafe extern "C-unwind" fn webview_create(state: *mut ffi::lua_State) -> c_int {
@@ -243,6 +77,9 @@ unsafe extern "C-unwind" fn webview_create(state: *mut ffi::lua_State) -> c_int
         push_wrapped_error(state, &format!("{}: incorrect number of arguments passed; expected 1 argument (table), got: {}", function_name, top));
     }
 
+    // SAFETY: we're called from Luau, so the API is safe to use again
+    unsafe { webview_ipc::release_collected_refs(state) };
+
     let t = unsafe { utils::type_of(state, -1) };
     if t != b"table" {
         push_wrapped_error(state, &format!("{}: expected table, got: {}", function_name, t));
@@ -260,16 +97,27 @@ unsafe extern "C-unwind" fn webview_create(state: *mut ffi::lua_State) -> c_int
     let (to_luau_tx, to_luau_rx) = crossbeam_channel::unbounded::<ToLuau>();
     let (to_window_tx, to_window_rx) = crossbeam_channel::unbounded::<ToWindow>();
 
-    std::thread::spawn(|| {
-        if let Err(err) = spawn(options, to_luau_tx, to_window_rx) {
-            eprintln!("webview.create: unable to spawn webview due to err: {}", err);
-        }
-    });
+    // the UI thread only needs the schemes, the handlers stay with the WebviewIpc
+    let protocols = options.protocols.clone();
 
-    let handler = Box::new(WebviewIpc {
-        sender: to_window_tx,
-        receiver: to_luau_rx,
+    // every window lives on the one UI thread, which tells us whether it could open this one
+    let opened = ui::WindowSender::new(to_window_tx).and_then(|to_window_tx| {
+        let created = ui::request(|reply| ui::UiRequest::CreateWindow(Box::new(options), to_luau_tx, to_window_rx, reply), None);
+        created.and_then(|created| created).map(|()| to_window_tx)
     });
+    let to_window_tx = match opened {
+        Ok(to_window_tx) => to_window_tx,
+        Err(err) => {
+            // nothing else will ever release the handlers
+            for function_ref in protocols.into_values() {
+                unsafe { ffi::lua_unref(state, function_ref) };
+            }
+            push_wrapped_error(state, &format!("{}: {}", function_name, err));
+            return 1;
+        }
+    };
+
+    let handler = Box::new(WebviewIpc::new(to_window_tx, to_luau_rx, protocols));
 
     let boxed = Box::into_raw(handler);
 
@@ -283,22 +131,122 @@ unsafe extern "C-unwind" fn webview_create(state: *mut ffi::lua_State) -> c_int
         push_wrapped_c_function(state, WebviewIpc::replace_html);
         ffi::lua_setfield(state, -2, c"replace_html".as_ptr());
 
+        push_wrapped_c_function(state, WebviewIpc::navigate);
+        ffi::lua_setfield(state, -2, c"navigate".as_ptr());
+
+        push_wrapped_c_function(state, WebviewIpc::register_asset);
+        ffi::lua_setfield(state, -2, c"register_asset".as_ptr());
+
+        push_wrapped_c_function(state, WebviewIpc::add_init_script);
+        ffi::lua_setfield(state, -2, c"add_init_script".as_ptr());
+
+        push_wrapped_c_function(state, WebviewIpc::eval);
+        ffi::lua_setfield(state, -2, c"eval".as_ptr());
+
+        push_wrapped_c_function(state, WebviewIpc::eval_result);
+        ffi::lua_setfield(state, -2, c"eval_result".as_ptr());
+
+        push_wrapped_c_function(state, WebviewIpc::send);
+        ffi::lua_setfield(state, -2, c"send".as_ptr());
+
+        push_wrapped_c_function(state, WebviewIpc::bind);
+        ffi::lua_setfield(state, -2, c"bind".as_ptr());
+
         push_wrapped_c_function(state, WebviewIpc::try_read);
         ffi::lua_setfield(state, -2, c"try_read".as_ptr());
 
+        push_wrapped_c_function(state, WebviewIpc::read);
+        ffi::lua_setfield(state, -2, c"read".as_ptr());
+
+        push_wrapped_c_function(state, WebviewIpc::set_title);
+        ffi::lua_setfield(state, -2, c"set_title".as_ptr());
+
+        push_wrapped_c_function(state, WebviewIpc::set_size);
+        ffi::lua_setfield(state, -2, c"set_size".as_ptr());
+
+        push_wrapped_c_function(state, WebviewIpc::set_position);
+        ffi::lua_setfield(state, -2, c"set_position".as_ptr());
+
+        push_wrapped_c_function(state, WebviewIpc::center);
+        ffi::lua_setfield(state, -2, c"center".as_ptr());
+
+        push_wrapped_c_function(state, WebviewIpc::set_min_size);
+        ffi::lua_setfield(state, -2, c"set_min_size".as_ptr());
+
+        push_wrapped_c_function(state, WebviewIpc::set_max_size);
+        ffi::lua_setfield(state, -2, c"set_max_size".as_ptr());
+
+        push_wrapped_c_function(state, WebviewIpc::set_resizable);
+        ffi::lua_setfield(state, -2, c"set_resizable".as_ptr());
+
+        push_wrapped_c_function(state, WebviewIpc::set_always_on_top);
+        ffi::lua_setfield(state, -2, c"set_always_on_top".as_ptr());
+
+        push_wrapped_c_function(state, WebviewIpc::set_fullscreen);
+        ffi::lua_setfield(state, -2, c"set_fullscreen".as_ptr());
+
+        push_wrapped_c_function(state, WebviewIpc::set_visible);
+        ffi::lua_setfield(state, -2, c"set_visible".as_ptr());
+
+        push_wrapped_c_function(state, WebviewIpc::focus);
+        ffi::lua_setfield(state, -2, c"focus".as_ptr());
+
+        push_wrapped_c_function(state, WebviewIpc::minimize);
+        ffi::lua_setfield(state, -2, c"minimize".as_ptr());
+
+        push_wrapped_c_function(state, WebviewIpc::maximize);
+        ffi::lua_setfield(state, -2, c"maximize".as_ptr());
+
+        push_wrapped_c_function(state, WebviewIpc::unmaximize);
+        ffi::lua_setfield(state, -2, c"unmaximize".as_ptr());
+
         push_wrapped_c_function(state, WebviewIpc::close);
         ffi::lua_setfield(state, -2, c"close".as_ptr());
 
+        push_wrapped_c_function(state, WebviewIpc::destroy);
+        ffi::lua_setfield(state, -2, c"destroy".as_ptr());
+
         push_wrapped_c_function(state, WebviewIpc::alert);
         ffi::lua_setfield(state, -2, c"alert".as_ptr());
 
         push_wrapped_c_function(state, WebviewIpc::size);
         ffi::lua_setfield(state, -2, c"size".as_ptr());
 
+        push_wrapped_c_function(state, WebviewIpc::position);
+        ffi::lua_setfield(state, -2, c"position".as_ptr());
+
+        push_wrapped_c_function(state, WebviewIpc::outer_size);
+        ffi::lua_setfield(state, -2, c"outer_size".as_ptr());
+
+        push_wrapped_c_function(state, WebviewIpc::is_maximized);
+        ffi::lua_setfield(state, -2, c"is_maximized".as_ptr());
+
+        push_wrapped_c_function(state, WebviewIpc::is_minimized);
+        ffi::lua_setfield(state, -2, c"is_minimized".as_ptr());
+
+        push_wrapped_c_function(state, WebviewIpc::is_focused);
+        ffi::lua_setfield(state, -2, c"is_focused".as_ptr());
+
+        push_wrapped_c_function(state, WebviewIpc::is_visible);
+        ffi::lua_setfield(state, -2, c"is_visible".as_ptr());
+
+        push_wrapped_c_function(state, WebviewIpc::is_fullscreen);
+        ffi::lua_setfield(state, -2, c"is_fullscreen".as_ptr());
+
+        push_wrapped_c_function(state, WebviewIpc::scale_factor);
+        ffi::lua_setfield(state, -2, c"scale_factor".as_ptr());
+
+        push_wrapped_c_function(state, WebviewIpc::title);
+        ffi::lua_setfield(state, -2, c"title".as_ptr());
+
+        push_wrapped_c_function(state, WebviewIpc::current_monitor);
+        ffi::lua_setfield(state, -2, c"current_monitor".as_ptr());
+
         ffi::lua_pushstring(state, c"WebviewIpc".as_ptr());
         ffi::lua_setfield(state, -2, c"__type".as_ptr()); // typeof(ud)
 
         ffi::lua_setuserdatametatable(state, 13);
+        ffi::lua_setuserdatadtor(state, webview_ipc::WEBVIEW_IPC_TAG, Some(webview_ipc::destructor));
 
         let ud = ffi::lua_newuserdatataggedwithmetatable(
             state, 
@@ -314,6 +262,44 @@ unsafe extern "C-unwind" fn webview_create(state: *mut ffi::lua_State) -> c_int
     1
 }
 
+unsafe extern "C-unwind" fn webview_monitors(state: *mut ffi::lua_State) -> c_int {
+    let function_name = "webseal.monitors()";
+
+    let monitors = match ui::request(ui::UiRequest::Monitors, Some(ui::UI_TIMEOUT)) {
+        Ok(monitors) => monitors,
+        Err(err) => {
+            push_wrapped_error(state, &format!("{}: {}", function_name, err));
+            return 1;
+        }
+    };
+
+    unsafe {
+        ffi::luaL_checkstack(state, 2, c"can't stack".as_ptr());
+        ffi::lua_createtable(state, monitors.len() as c_int, 0);
+        for (i, monitor) in monitors.iter().enumerate() {
+            monitor.push(state);
+            ffi::lua_rawseti(state, -2, (i + 1) as c_int);
+        }
+    }
+
+    1
+}
+
+unsafe extern "C-unwind" fn webview_primary_monitor(state: *mut ffi::lua_State) -> c_int {
+    let function_name = "webseal.primary_monitor()";
+
+    match ui::request(ui::UiRequest::PrimaryMonitor, Some(ui::UI_TIMEOUT)) {
+        Ok(Some(monitor)) => unsafe { monitor.push(state) },
+        // some platforms (Wayland) have no notion of a primary monitor
+        Ok(None) => unsafe { ffi::lua_pushnil(state) },
+        Err(err) => {
+            push_wrapped_error(state, &format!("{}: {}", function_name, err));
+        }
+    }
+
+    1
+}
+
 /// The entrypoint to an extern library/plugin for the seal runtime.
 /// 
 /// This function must return one value on the Luau stack,
@@ -338,6 +324,15 @@ pub unsafe extern "C-unwind" fn seal_open_extern(state: *mut ffi::lua_State, ptr
         push_wrapped_c_function(state, webview_create);
         ffi::lua_setfield(state, -2, c"create".as_ptr());
 
+        push_wrapped_c_function(state, webview_monitors);
+        ffi::lua_setfield(state, -2, c"monitors".as_ptr());
+
+        push_wrapped_c_function(state, webview_primary_monitor);
+        ffi::lua_setfield(state, -2, c"primary_monitor".as_ptr());
+
+        json::push_null(state);
+        ffi::lua_setfield(state, -2, c"null".as_ptr());
+
         // table left on stack
     }
     1
@@ -0,0 +1,286 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::ffi::c_int;
+
+use bstr::ByteSlice;
+use seal::ffi;
+use wry::RequestAsyncResponder;
+use wry::http::{
+    HeaderName, HeaderValue, Request, Response, StatusCode,
+    header::{ACCESS_CONTROL_ALLOW_ORIGIN, CONTENT_TYPE},
+};
+
+use crate::utils;
+
+/// Schemes the webview already handles, or that webseal serves itself.
+const RESERVED_SCHEMES: &[&str] = &[
+    crate::protocol::SCHEME, "http", "https", "file", "data", "blob", "about", "javascript", "ws", "wss", "ftp",
+];
+
+/// Checks that `scheme` can be used for a protocol implemented in Luau.
+pub fn validate_scheme(scheme: &str) -> Result<(), String> {
+    let mut chars = scheme.chars();
+    let valid = chars.next().is_some_and(|c| c.is_ascii_lowercase())
+        && chars.all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '+' | '-' | '.'));
+    if !valid {
+        return Err(format!("invalid scheme {:?} (expected lowercase letters, digits, '+', '-' or '.', starting with a letter)", scheme));
+    }
+    if RESERVED_SCHEMES.contains(&scheme) {
+        return Err(format!("the {:?} scheme is reserved", scheme));
+    }
+    Ok(())
+}
+
+/// Requests to protocols implemented in Luau, waiting on `ToWindow::ProtocolResponse`.
+///
+/// Lives on the UI thread, since responders have to be answered from the thread the webview runs on.
+#[derive(Default)]
+pub struct PendingRequests {
+    next_id: Cell<u64>,
+    responders: RefCell<HashMap<u64, RequestAsyncResponder>>,
+}
+impl PendingRequests {
+    pub fn next_id(&self) -> u64 {
+        let id = self.next_id.get();
+        self.next_id.set(id.wrapping_add(1));
+        id
+    }
+    pub fn insert(&self, id: u64, responder: RequestAsyncResponder) {
+        self.responders.borrow_mut().insert(id, responder);
+    }
+    /// Answers the request with the same id; does nothing if it was already answered.
+    pub fn respond(&self, id: u64, response: Response<Vec<u8>>) {
+        if let Some(responder) = self.responders.borrow_mut().remove(&id) {
+            responder.respond(response);
+        }
+    }
+}
+
+/// Plain text response, for when the Luau handler couldn't answer.
+pub fn error_response(status: StatusCode, message: String) -> Response<Vec<u8>> {
+    let mut response = Response::new(message.into_bytes());
+    *response.status_mut() = status;
+    let headers = response.headers_mut();
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static("text/plain; charset=utf-8"));
+    headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, HeaderValue::from_static("*"));
+    response
+}
+
+/// What a Luau handler sees of a request, see `push_request`.
+struct LuauRequest<'a> {
+    method: &'a str,
+    url: String,
+    /// The same on every platform, unlike `url` (see `protocol::app_url`).
+    path: &'a str,
+    /// Names are lowercase, and repeated headers are joined with ", ".
+    headers: Vec<(&'a str, String)>,
+    /// Only filled in on Linux with wry's linux-body feature; it's always empty there otherwise.
+    body: &'a [u8],
+}
+impl<'a> LuauRequest<'a> {
+    fn new(request: &'a Request<Vec<u8>>) -> Self {
+        let mut headers: Vec<(&str, String)> = Vec::new();
+        for (name, value) in request.headers() {
+            let value = value.as_bytes().to_str_lossy();
+            match headers.iter_mut().find(|(existing, _)| *existing == name.as_str()) {
+                Some((_, existing)) => {
+                    existing.push_str(", ");
+                    existing.push_str(&value);
+                },
+                None => headers.push((name.as_str(), value.into_owned())),
+            }
+        }
+        Self {
+            method: request.method().as_str(),
+            url: request.uri().to_string(),
+            path: request.uri().path(),
+            headers,
+            body: request.body(),
+        }
+    }
+}
+
+/// Pushes `request` onto the Luau stack as `{ method, url, path, headers = { [name]: value }, body }`.
+///
+/// # Safety
+/// - state must be a non-null pointer to a lua_State
+pub unsafe fn push_request(state: *mut ffi::lua_State, request: &Request<Vec<u8>>) {
+    let LuauRequest { method, url, path, headers, body } = LuauRequest::new(request);
+    unsafe {
+        ffi::luaL_checkstack(state, 4, c"can't push protocol request".as_ptr());
+        ffi::lua_createtable(state, 0, 5);
+
+        ffi::lua_pushlstring(state, method.as_ptr() as *const _, method.len());
+        ffi::lua_setfield(state, -2, c"method".as_ptr());
+
+        ffi::lua_pushlstring(state, url.as_ptr() as *const _, url.len());
+        ffi::lua_setfield(state, -2, c"url".as_ptr());
+
+        ffi::lua_pushlstring(state, path.as_ptr() as *const _, path.len());
+        ffi::lua_setfield(state, -2, c"path".as_ptr());
+
+        ffi::lua_createtable(state, 0, headers.len() as c_int);
+        for (name, value) in &headers {
+            ffi::lua_pushlstring(state, name.as_ptr() as *const _, name.len());
+            ffi::lua_pushlstring(state, value.as_ptr() as *const _, value.len());
+            ffi::lua_rawset(state, -3);
+        }
+        ffi::lua_setfield(state, -2, c"headers".as_ptr());
+
+        ffi::lua_pushlstring(state, body.as_ptr() as *const _, body.len());
+        ffi::lua_setfield(state, -2, c"body".as_ptr());
+    }
+}
+
+/// Reads the `{ status, headers, body }` a Luau handler returned at the top of the stack, without popping it.
+///
+/// `status` defaults to 200, and pages can read the response from any origin unless the handler
+/// sets its own `access-control-allow-origin`.
+///
+/// # Safety
+/// - state must be a non-null pointer to a lua_State
+pub unsafe fn response_from_stack(state: *mut ffi::lua_State) -> Result<Response<Vec<u8>>, String> {
+    if unsafe { ffi::lua_type(state, -1) } != ffi::LUA_TTABLE {
+        let got_t = unsafe { utils::type_of(state, -1) };
+        return Err(format!("expected the handler to return a table, got {}", got_t));
+    }
+
+    let mut response = Response::new(Vec::new());
+    unsafe { ffi::luaL_checkstack(state, 3, c"can't read protocol response".as_ptr()) };
+
+    let status_type = unsafe { ffi::lua_getfield(state, -1, c"status".as_ptr()) };
+    let status = if status_type == ffi::LUA_TNUMBER {
+        let status = unsafe { ffi::lua_tonumber(state, -1) };
+        match StatusCode::from_u16(status as u16) {
+            // the cast saturates, so anything out of range or fractional is still caught
+            Ok(code) if status.fract() == 0.0 => Ok(code),
+            _ => Err(format!("invalid status {}", status)),
+        }
+    } else if status_type == ffi::LUA_TNIL {
+        Ok(StatusCode::OK)
+    } else {
+        let got_t = unsafe { utils::type_of(state, -1) };
+        Err(format!("expected 'status' to be a number or nil, got {}", got_t))
+    };
+    unsafe { ffi::lua_pop(state, 1) };
+    *response.status_mut() = status?;
+
+    let body_type = unsafe { ffi::lua_getfield(state, -1, c"body".as_ptr()) };
+    let body = match unsafe { utils::bytes_at(state, -1) } {
+        Some(body) => Ok(body),
+        None if body_type == ffi::LUA_TNIL => Ok(Vec::new()),
+        None => {
+            let got_t = unsafe { utils::type_of(state, -1) };
+            Err(format!("expected 'body' to be a string, buffer or nil, got {}", got_t))
+        },
+    };
+    unsafe { ffi::lua_pop(state, 1) };
+    *response.body_mut() = body?;
+
+    let headers_type = unsafe { ffi::lua_getfield(state, -1, c"headers".as_ptr()) };
+    if headers_type == ffi::LUA_TTABLE {
+        unsafe { ffi::lua_pushnil(state) };
+        // stack: [ response, headers, name, value ] on every iteration
+        while unsafe { ffi::lua_next(state, -2) } != 0 {
+            let header = unsafe { header_from_stack(state) };
+            // pop value, keep name for lua_next
+            unsafe { ffi::lua_pop(state, 1) };
+            match header {
+                Ok((name, value)) => {
+                    response.headers_mut().append(name, value);
+                },
+                Err(err) => {
+                    // pop name and headers
+                    unsafe { ffi::lua_pop(state, 2) };
+                    return Err(err);
+                },
+            }
+        }
+    } else if headers_type != ffi::LUA_TNIL {
+        let got_t = unsafe { utils::type_of(state, -1) };
+        unsafe { ffi::lua_pop(state, 1) };
+        return Err(format!("expected 'headers' to be a table or nil, got {}", got_t));
+    }
+    // pop headers table or nil
+    unsafe { ffi::lua_pop(state, 1) };
+
+    if !response.headers().contains_key(ACCESS_CONTROL_ALLOW_ORIGIN) {
+        // pages are served from seal://app/ or elsewhere, which is always a different origin
+        response.headers_mut().insert(ACCESS_CONTROL_ALLOW_ORIGIN, HeaderValue::from_static("*"));
+    }
+    Ok(response)
+}
+
+/// Reads the header name at -2 and value at -1 on the Luau stack, without popping them.
+///
+/// # Safety
+/// - state must be a non-null pointer to a lua_State
+unsafe fn header_from_stack(state: *mut ffi::lua_State) -> Result<(HeaderName, HeaderValue), String> {
+    if unsafe { ffi::lua_type(state, -2) } != ffi::LUA_TSTRING {
+        let got_t = unsafe { utils::type_of(state, -2) };
+        return Err(format!("expected header names to be strings, got {}", got_t));
+    }
+    // SAFETY: checked to be a string above, so bytes_at can't return None
+    let name = unsafe { utils::bytes_at(state, -2) }.unwrap_or_default();
+    let Ok(name) = HeaderName::from_bytes(&name) else {
+        return Err(format!("invalid header name {:?}", name.to_str_lossy()));
+    };
+    let value = if unsafe { ffi::lua_type(state, -1) } == ffi::LUA_TSTRING {
+        unsafe { utils::bytes_at(state, -1) }.unwrap_or_default()
+    } else {
+        let got_t = unsafe { utils::type_of(state, -1) };
+        return Err(format!("expected header {:?} to be a string, got {}", name.as_str(), got_t));
+    };
+    match HeaderValue::from_bytes(&value) {
+        Ok(value) => Ok((name, value)),
+        Err(_) => Err(format!("invalid value for header {:?}", name.as_str())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn passes_the_body_and_headers_along() {
+        let request = Request::builder()
+            .method("POST")
+            .uri("api://localhost/items?page=2")
+            .header("Content-Type", "application/json")
+            .header("x-tag", "a")
+            .header("x-tag", "b")
+            .body(br#"{"name":"thing"}"#.to_vec())
+            .unwrap();
+        let fields = LuauRequest::new(&request);
+        assert_eq!(fields.method, "POST");
+        assert_eq!(fields.url, "api://localhost/items?page=2");
+        assert_eq!(fields.path, "/items");
+        assert_eq!(fields.body, br#"{"name":"thing"}"#);
+        assert_eq!(fields.headers, vec![
+            ("content-type", String::from("application/json")),
+            ("x-tag", String::from("a, b")),
+        ]);
+    }
+
+    #[test]
+    fn accepts_custom_schemes() {
+        assert!(validate_scheme("api").is_ok());
+        assert!(validate_scheme("my-app.v2+json").is_ok());
+    }
+
+    #[test]
+    fn rejects_invalid_schemes() {
+        assert!(validate_scheme("").is_err());
+        assert!(validate_scheme("2api").is_err());
+        assert!(validate_scheme("Api").is_err());
+        assert!(validate_scheme("my app").is_err());
+        assert!(validate_scheme("api:").is_err());
+    }
+
+    #[test]
+    fn rejects_reserved_schemes() {
+        for scheme in RESERVED_SCHEMES {
+            assert!(validate_scheme(scheme).is_err(), "{} should be reserved", scheme);
+        }
+    }
+}
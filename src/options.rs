@@ -1,17 +1,145 @@
+use std::collections::HashMap;
+use std::ffi::CStr;
+use std::path::{Path, PathBuf};
+
 use libc::c_int;
 use bstr::{BString, ByteSlice};
 
 use crate::ffi;
 
+use crate::luau_protocol;
+use crate::protocol::{self, Asset};
 use crate::utils::*;
 
+/// How messages posted from JS with `window.ipc.postMessage` are handed to Luau.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IpcMode {
+    /// Messages are passed to Luau as-is, as strings.
+    String,
+    /// Messages are decoded as JSON and passed to Luau as Luau values;
+    /// messages that aren't valid JSON are passed as strings.
+    Json,
+}
+
+/// What the window shows when it opens.
+pub enum Content {
+    /// Inline HTML from the `html` option, wrapped in template.html or bare.html depending on `Decorations`
+    /// and served at the root of `seal://app/`.
+    Html(String),
+    /// A page to load as-is from the `url` option, or a `file://` URL made from the `file` option
+    /// (or the app's index.html if neither is passed, see `protocol::SealProtocol`).
+    Url(String),
+}
+
+/// Resolves a relative `path` against the directory of the script calling webseal.create;
+/// errors if we can't tell what that is.
+///
+/// # Safety
+/// - `state` must be a pointer to a non-null Luau state
+unsafe fn script_relative(state: *mut ffi::lua_State, path: &str) -> Result<PathBuf, String> {
+    let path = Path::new(path);
+    if path.is_absolute() {
+        return Ok(path.to_path_buf());
+    }
+    unsafe { script_dir(state) }.map(|dir| dir.join(path))
+}
+
+/// Turns `path` into an absolute `file://` URL; errors if the file doesn't exist.
+fn file_url(path: &Path) -> std::io::Result<String> {
+    let path = std::fs::canonicalize(path)?;
+    let path = path.to_string_lossy();
+    // canonicalize gives verbatim paths like \\?\C:\app on windows
+    let path = path.strip_prefix(r"\\?\").unwrap_or(&path).replace('\\', "/");
+    let mut url = String::from("file://");
+    if !path.starts_with('/') {
+        url.push('/');
+    }
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' | b':' => url.push(byte as char),
+            _ => url.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    Ok(url)
+}
+
+/// What frames the window.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Decorations {
+    /// No native decorations; the page is wrapped in webseal's own titlebar from template.html.
+    /// Only works with inline `html`, and is the default for it.
+    Custom,
+    /// The window manager's titlebar and borders, with the page as-is.
+    /// The default for `url` and `file`.
+    Native,
+    /// No decorations at all; the page is as-is and draws everything itself.
+    None,
+}
+
+/// Native window events forwarded to Luau as `read`/`try_read` events.
+///
+/// These can be noisy (resizing fires on every frame), so they're all opt-in with the `events` option.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct EventMask(u8);
+impl EventMask {
+    pub const RESIZED: Self = Self(1 << 0);
+    pub const MOVED: Self = Self(1 << 1);
+    pub const FOCUSED: Self = Self(1 << 2);
+    pub const SCALE_FACTOR_CHANGED: Self = Self(1 << 3);
+    pub const THEME_CHANGED: Self = Self(1 << 4);
+    pub const FILE_DROPPED: Self = Self(1 << 5);
+    /// Covers both `file_hovered` and `file_hover_cancelled` events.
+    pub const FILE_HOVERED: Self = Self(1 << 6);
+
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "resized" => Some(Self::RESIZED),
+            "moved" => Some(Self::MOVED),
+            "focused" => Some(Self::FOCUSED),
+            "scale_factor_changed" => Some(Self::SCALE_FACTOR_CHANGED),
+            "theme_changed" => Some(Self::THEME_CHANGED),
+            "file_dropped" => Some(Self::FILE_DROPPED),
+            "file_hovered" => Some(Self::FILE_HOVERED),
+            _ => None,
+        }
+    }
+}
+
 pub struct WebviewOptions {
     pub title: String,
-    pub html: String,
+    pub content: Content,
+    /// Canonicalized directory served over `seal://app/`, see `protocol::SealProtocol`.
+    pub root_dir: Option<PathBuf>,
+    /// Served over `seal://app/` along with `root_dir`, keyed by path.
+    pub assets: Vec<(String, Asset)>,
     pub size: (f32, f32),
     pub resizeable: bool,
     pub max_size: Option<(f32, f32)>,
     pub min_size: Option<(f32, f32)>,
+    pub ipc_mode: IpcMode,
+    pub events: EventMask,
+    pub decorations: Decorations,
+    /// Outer position in physical pixels, like `WebviewIpc:set_position()`.
+    pub position: Option<(f32, f32)>,
+    pub center: bool,
+    pub maximized: bool,
+    pub fullscreen: bool,
+    pub always_on_top: bool,
+    pub always_on_bottom: bool,
+    pub visible: bool,
+    pub focused: bool,
+    pub skip_taskbar: bool,
+    /// Defaults to whether the window uses `Decorations::Custom`, whose rounded corners need it.
+    pub transparent: Option<bool>,
+    pub visible_on_all_workspaces: bool,
+    /// Run before the page's own scripts on every page load, after `window.seal` is defined.
+    pub init_scripts: Vec<String>,
+    /// Registry refs to the Luau functions answering requests to each scheme, see `luau_protocol`.
+    /// Released by `WebviewIpc:destroy()`, or by `webseal.create` if the window can't be opened.
+    pub protocols: HashMap<String, c_int>,
 }
 impl WebviewOptions {
     /// SAFETY: element at stack idx -1 must be a vector
@@ -29,6 +157,49 @@ impl WebviewOptions {
         let y = unsafe { *y_ptr };
         (x, y)
     }
+    /// Gets the optional string field `field` from the table at stack idx -1;
+    /// pushes a wrapped error if it's something other than a string or nil.
+    ///
+    /// # Safety
+    /// - `state` must be a pointer to a non-null Luau state
+    /// - The value at stack index -1 must be a Luau table.
+    unsafe fn string_field(state: *mut ffi::lua_State, field: &CStr, function_name: &'static str) -> Result<Option<String>, c_int> {
+        let field_type = unsafe { ffi::lua_getfield(state, -1, field.as_ptr()) };
+        let s = if field_type == ffi::LUA_TSTRING {
+            let ptr = unsafe { ffi::lua_tostring(state, -1) };
+            Some(unsafe { BString::clone_from_ptr(ptr) }.to_str_lossy().to_string())
+        } else if field_type == ffi::LUA_TNIL {
+            None
+        } else {
+            let got_t = unsafe { type_of(state, -1) };
+            unsafe { ffi::lua_pop(state, 1) };
+            push_wrapped_error(state, &format!("{}: expected '{}' to be a string or nil, got {}", function_name, field.to_string_lossy(), got_t));
+            return Err(1);
+        };
+        unsafe { ffi::lua_pop(state, 1) };
+        Ok(s)
+    }
+    /// Gets the optional boolean field `field` from the table at stack idx -1;
+    /// pushes a wrapped error if it's something other than a boolean or nil.
+    ///
+    /// # Safety
+    /// - `state` must be a pointer to a non-null Luau state
+    /// - The value at stack index -1 must be a Luau table.
+    unsafe fn bool_field(state: *mut ffi::lua_State, field: &CStr, function_name: &'static str) -> Result<Option<bool>, c_int> {
+        let field_type = unsafe { ffi::lua_getfield(state, -1, field.as_ptr()) };
+        let b = if field_type == ffi::LUA_TBOOLEAN {
+            Some(unsafe { ffi::lua_toboolean(state, -1) } != 0)
+        } else if field_type == ffi::LUA_TNIL {
+            None
+        } else {
+            let got_t = unsafe { type_of(state, -1) };
+            unsafe { ffi::lua_pop(state, 1) };
+            push_wrapped_error(state, &format!("{}: expected '{}' to be a boolean or nil, got {}", function_name, field.to_string_lossy(), got_t));
+            return Err(1);
+        };
+        unsafe { ffi::lua_pop(state, 1) };
+        Ok(b)
+    }
     /// Extracts relevant values from the table passed to webview.create;
     /// - If there's an error, pushes the wrapped_error onto the stack
     /// - If there's a passed event handler function, pushes it to the Luau registry as `WEBSEAL_WEBVIEW_HANDLER`
@@ -47,16 +218,100 @@ impl WebviewOptions {
         // get rid of title to balance stack
         unsafe { ffi::lua_pop(state, 1) };
 
-        let html_type = unsafe { ffi::lua_getfield(state, -1, c"html".as_ptr()) };
-        let html = if html_type == ffi::LUA_TSTRING {
-            let ptr = unsafe { ffi::lua_tostring(state, -1) };
-            let s = unsafe { BString::clone_from_ptr(ptr) }.to_str_lossy().to_string();
-            // get rid of html to balance stack
+        let html = unsafe { Self::string_field(state, c"html", function_name)? };
+        let url = unsafe { Self::string_field(state, c"url", function_name)? };
+        let file = unsafe { Self::string_field(state, c"file", function_name)? };
+
+        let root_dir = match unsafe { Self::string_field(state, c"root_dir", function_name)? } {
+            Some(root_dir) => {
+                let path = match unsafe { script_relative(state, &root_dir) } {
+                    Ok(path) => path,
+                    Err(err) => {
+                        push_wrapped_error(state, &format!("{}: unable to resolve 'root_dir' {} due to err: {}", function_name, root_dir, err));
+                        return Err(1);
+                    },
+                };
+                match std::fs::canonicalize(&path) {
+                    Ok(root_dir) if root_dir.is_dir() => Some(root_dir),
+                    Ok(_) => {
+                        push_wrapped_error(state, &format!("{}: 'root_dir' {} isn't a directory", function_name, path.display()));
+                        return Err(1);
+                    },
+                    Err(err) => {
+                        push_wrapped_error(state, &format!("{}: unable to open 'root_dir' {} due to err: {}", function_name, path.display(), err));
+                        return Err(1);
+                    },
+                }
+            },
+            None => None,
+        };
+
+        let assets_type = unsafe { ffi::lua_getfield(state, -1, c"assets".as_ptr()) };
+        let mut assets = Vec::new();
+        if assets_type == ffi::LUA_TTABLE {
+            unsafe { ffi::lua_pushnil(state) };
+            // stack: [ options, assets, key, value ] on every iteration
+            while unsafe { ffi::lua_next(state, -2) } != 0 {
+                let path = if unsafe { ffi::lua_type(state, -2) } == ffi::LUA_TSTRING {
+                    let ptr = unsafe { ffi::lua_tostring(state, -2) };
+                    unsafe { BString::clone_from_ptr(ptr) }.to_str_lossy().to_string()
+                } else {
+                    let got_t = unsafe { type_of(state, -2) };
+                    // pop value, key and assets
+                    unsafe { ffi::lua_pop(state, 3) };
+                    push_wrapped_error(state, &format!("{}: expected the keys of 'assets' to be paths (strings), got {}", function_name, got_t));
+                    return Err(1);
+                };
+                let Some(contents) = (unsafe { bytes_at(state, -1) }) else {
+                    let got_t = unsafe { type_of(state, -1) };
+                    unsafe { ffi::lua_pop(state, 3) };
+                    push_wrapped_error(state, &format!("{}: expected asset {:?} to be a string or buffer, got {}", function_name, path, got_t));
+                    return Err(1);
+                };
+                match Asset::new(&path, contents, None) {
+                    Ok(asset) => assets.push((path, asset)),
+                    Err(err) => {
+                        unsafe { ffi::lua_pop(state, 3) };
+                        push_wrapped_error(state, &format!("{}: asset {:?}: {}", function_name, path, err));
+                        return Err(1);
+                    }
+                }
+                // pop value, keep key for lua_next
+                unsafe { ffi::lua_pop(state, 1) };
+            }
+        } else if assets_type != ffi::LUA_TNIL {
+            let got_t = unsafe { type_of(state, -1) };
             unsafe { ffi::lua_pop(state, 1) };
-            s
-        } else {
-            push_wrapped_error(state, &format!("{}: missing or incorrect table field 'html' (got {})", function_name, unsafe { type_of(state, -1) }));
+            push_wrapped_error(state, &format!("{}: expected 'assets' to be a table or nil, got {}", function_name, got_t));
             return Err(1);
+        }
+        // pop assets table or nil
+        unsafe { ffi::lua_pop(state, 1) };
+
+        let content = match (html, url, file) {
+            (Some(html), None, None) => Content::Html(html),
+            (None, Some(url), None) => Content::Url(url),
+            (None, None, Some(file)) => {
+                let path = match unsafe { script_relative(state, &file) } {
+                    Ok(path) => path,
+                    Err(err) => {
+                        push_wrapped_error(state, &format!("{}: unable to resolve 'file' {} due to err: {}", function_name, file, err));
+                        return Err(1);
+                    },
+                };
+                match file_url(&path) {
+                    Ok(url) => Content::Url(url),
+                    Err(err) => {
+                        push_wrapped_error(state, &format!("{}: unable to open 'file' {} due to err: {}", function_name, path.display(), err));
+                        return Err(1);
+                    }
+                }
+            },
+            (None, None, None) if root_dir.is_some() || !assets.is_empty() => Content::Url(protocol::app_url("")),
+            _ => {
+                push_wrapped_error(state, &format!("{}: expected exactly one of 'html', 'url' or 'file' (or just 'root_dir' or 'assets')", function_name));
+                return Err(1);
+            }
         };
 
         let size_type = unsafe { ffi::lua_getfield(state, -1, c"size".as_ptr()) };
@@ -97,13 +352,220 @@ impl WebviewOptions {
         };
         unsafe { ffi::lua_pop(state, 1) };
 
+        let ipc_mode_type = unsafe { ffi::lua_getfield(state, -1, c"ipc_mode".as_ptr()) };
+        let ipc_mode = if ipc_mode_type == ffi::LUA_TSTRING {
+            let ptr = unsafe { ffi::lua_tostring(state, -1) };
+            let s = unsafe { BString::clone_from_ptr(ptr) }.to_str_lossy().to_string();
+            unsafe { ffi::lua_pop(state, 1) };
+            match s.as_str() {
+                "string" => IpcMode::String,
+                "json" => IpcMode::Json,
+                other => {
+                    push_wrapped_error(state, &format!("{}: expected 'ipc_mode' to be \"string\" or \"json\", got \"{}\"", function_name, other));
+                    return Err(1);
+                }
+            }
+        } else {
+            unsafe { ffi::lua_pop(state, 1) };
+            IpcMode::String
+        };
+
+        let events_type = unsafe { ffi::lua_getfield(state, -1, c"events".as_ptr()) };
+        let events = if events_type == ffi::LUA_TTABLE {
+            let mut events = EventMask::default();
+            let len = unsafe { ffi::lua_objlen(state, -1) };
+            for i in 1..=len {
+                let name_type = unsafe { ffi::lua_rawgeti(state, -1, i as c_int) };
+                if name_type != ffi::LUA_TSTRING {
+                    let got_t = unsafe { type_of(state, -1) };
+                    // pop entry and events table
+                    unsafe { ffi::lua_pop(state, 2) };
+                    push_wrapped_error(state, &format!("{}: expected 'events' to only contain strings, got {} at index {}", function_name, got_t, i));
+                    return Err(1);
+                }
+                let ptr = unsafe { ffi::lua_tostring(state, -1) };
+                let name = unsafe { BString::clone_from_ptr(ptr) }.to_str_lossy().to_string();
+                // pop name
+                unsafe { ffi::lua_pop(state, 1) };
+                match EventMask::from_name(&name) {
+                    Some(event) => events = EventMask(events.0 | event.0),
+                    None => {
+                        // pop events table
+                        unsafe { ffi::lua_pop(state, 1) };
+                        push_wrapped_error(state, &format!("{}: unknown event {:?} in 'events' (expected \"resized\", \"moved\", \"focused\", \"scale_factor_changed\", \"theme_changed\", \"file_dropped\" or \"file_hovered\")", function_name, name));
+                        return Err(1);
+                    }
+                }
+            }
+            events
+        } else {
+            EventMask::default()
+        };
+        unsafe { ffi::lua_pop(state, 1) };
+
+        let decorations_type = unsafe { ffi::lua_getfield(state, -1, c"decorations".as_ptr()) };
+        let decorations = if decorations_type == ffi::LUA_TSTRING {
+            let ptr = unsafe { ffi::lua_tostring(state, -1) };
+            let s = unsafe { BString::clone_from_ptr(ptr) }.to_str_lossy().to_string();
+            unsafe { ffi::lua_pop(state, 1) };
+            match s.as_str() {
+                "custom" if matches!(content, Content::Url(_)) => {
+                    push_wrapped_error(state, &format!("{}: the \"custom\" titlebar can only wrap inline 'html'; use \"native\" or \"none\" decorations with 'url' or 'file'", function_name));
+                    return Err(1);
+                },
+                "custom" => Decorations::Custom,
+                "native" => Decorations::Native,
+                "none" => Decorations::None,
+                other => {
+                    push_wrapped_error(state, &format!("{}: expected 'decorations' to be \"custom\", \"native\" or \"none\", got \"{}\"", function_name, other));
+                    return Err(1);
+                }
+            }
+        } else {
+            unsafe { ffi::lua_pop(state, 1) };
+            match content {
+                Content::Html(_) => Decorations::Custom,
+                Content::Url(_) => Decorations::Native,
+            }
+        };
+
+        let position_type = unsafe { ffi::lua_getfield(state, -1, c"position".as_ptr()) };
+        let position = if position_type == ffi::LUA_TVECTOR {
+            Some(unsafe { Self::x_and_y_from_vector(state) })
+        } else {
+            None
+        };
+        unsafe { ffi::lua_pop(state, 1) };
+
+        let center = unsafe { Self::bool_field(state, c"center", function_name)? }.unwrap_or(false);
+        if center && position.is_some() {
+            push_wrapped_error(state, &format!("{}: 'position' and 'center' can't both be set", function_name));
+            return Err(1);
+        }
+
+        let maximized = unsafe { Self::bool_field(state, c"maximized", function_name)? }.unwrap_or(false);
+        let fullscreen = unsafe { Self::bool_field(state, c"fullscreen", function_name)? }.unwrap_or(false);
+
+        let always_on_top = unsafe { Self::bool_field(state, c"always_on_top", function_name)? }.unwrap_or(false);
+        let always_on_bottom = unsafe { Self::bool_field(state, c"always_on_bottom", function_name)? }.unwrap_or(false);
+        if always_on_top && always_on_bottom {
+            push_wrapped_error(state, &format!("{}: 'always_on_top' and 'always_on_bottom' can't both be true", function_name));
+            return Err(1);
+        }
+
+        let visible = unsafe { Self::bool_field(state, c"visible", function_name)? }.unwrap_or(true);
+        let focused = unsafe { Self::bool_field(state, c"focused", function_name)? }.unwrap_or(true);
+        let skip_taskbar = unsafe { Self::bool_field(state, c"skip_taskbar", function_name)? }.unwrap_or(false);
+        let transparent = unsafe { Self::bool_field(state, c"transparent", function_name)? };
+        let visible_on_all_workspaces = unsafe { Self::bool_field(state, c"visible_on_all_workspaces", function_name)? }.unwrap_or(false);
+
+        let init_scripts_type = unsafe { ffi::lua_getfield(state, -1, c"init_scripts".as_ptr()) };
+        let mut init_scripts = Vec::new();
+        if init_scripts_type == ffi::LUA_TTABLE {
+            let len = unsafe { ffi::lua_objlen(state, -1) };
+            for i in 1..=len {
+                let script_type = unsafe { ffi::lua_rawgeti(state, -1, i as c_int) };
+                if script_type != ffi::LUA_TSTRING {
+                    let got_t = unsafe { type_of(state, -1) };
+                    // pop script and init_scripts table
+                    unsafe { ffi::lua_pop(state, 2) };
+                    push_wrapped_error(state, &format!("{}: expected 'init_scripts' to only contain strings, got {} at index {}", function_name, got_t, i));
+                    return Err(1);
+                }
+                let ptr = unsafe { ffi::lua_tostring(state, -1) };
+                init_scripts.push(unsafe { BString::clone_from_ptr(ptr) }.to_str_lossy().to_string());
+                // pop script
+                unsafe { ffi::lua_pop(state, 1) };
+            }
+        } else if init_scripts_type != ffi::LUA_TNIL {
+            let got_t = unsafe { type_of(state, -1) };
+            unsafe { ffi::lua_pop(state, 1) };
+            push_wrapped_error(state, &format!("{}: expected 'init_scripts' to be a table of strings or nil, got {}", function_name, got_t));
+            return Err(1);
+        }
+        // pop init_scripts table or nil
+        unsafe { ffi::lua_pop(state, 1) };
+
+        // last, so nothing after it can fail and leak the refs
+        let protocols = unsafe { Self::protocols_field(state, function_name)? };
+
         Ok(Self {
             title,
-            html,
+            content,
+            root_dir,
+            assets,
             size,
             resizeable,
             min_size,
             max_size,
+            ipc_mode,
+            events,
+            decorations,
+            position,
+            center,
+            maximized,
+            fullscreen,
+            always_on_top,
+            always_on_bottom,
+            visible,
+            focused,
+            skip_taskbar,
+            transparent,
+            visible_on_all_workspaces,
+            init_scripts,
+            protocols,
         })
     }
+
+    /// Gets `{ [scheme]: handler }` from the `protocols` field of the table at -1, taking a ref to each handler.
+    ///
+    /// Pushes a wrapped error message onto the Luau stack (releasing any refs taken so far) if it's malformed.
+    ///
+    /// # Safety
+    /// - `state` must be a pointer to a non-null Luau state with a table at -1
+    unsafe fn protocols_field(state: *mut ffi::lua_State, function_name: &'static str) -> Result<HashMap<String, c_int>, c_int> {
+        let protocols_type = unsafe { ffi::lua_getfield(state, -1, c"protocols".as_ptr()) };
+        let mut protocols = HashMap::new();
+        if protocols_type == ffi::LUA_TTABLE {
+            unsafe { ffi::lua_pushnil(state) };
+            // stack: [ options, protocols, scheme, handler ] on every iteration
+            while unsafe { ffi::lua_next(state, -2) } != 0 {
+                let error = if unsafe { ffi::lua_type(state, -2) } != ffi::LUA_TSTRING {
+                    let got_t = unsafe { type_of(state, -2) };
+                    Some(format!("expected the keys of 'protocols' to be schemes (strings), got {}", got_t))
+                } else {
+                    let ptr = unsafe { ffi::lua_tostring(state, -2) };
+                    let scheme = unsafe { BString::clone_from_ptr(ptr) }.to_str_lossy().to_string();
+                    if let Err(err) = luau_protocol::validate_scheme(&scheme) {
+                        Some(err)
+                    } else if unsafe { ffi::lua_type(state, -1) } != ffi::LUA_TFUNCTION {
+                        let got_t = unsafe { type_of(state, -1) };
+                        Some(format!("expected the handler for {:?} to be a function, got {}", scheme, got_t))
+                    } else {
+                        protocols.insert(scheme, unsafe { ffi::lua_ref(state, -1) });
+                        None
+                    }
+                };
+                if let Some(error) = error {
+                    for function_ref in protocols.into_values() {
+                        unsafe { ffi::lua_unref(state, function_ref) };
+                    }
+                    // pop handler, scheme and protocols
+                    unsafe { ffi::lua_pop(state, 3) };
+                    push_wrapped_error(state, &format!("{}: 'protocols': {}", function_name, error));
+                    return Err(1);
+                }
+                // pop handler, keep scheme for lua_next
+                unsafe { ffi::lua_pop(state, 1) };
+            }
+        } else if protocols_type != ffi::LUA_TNIL {
+            let got_t = unsafe { type_of(state, -1) };
+            unsafe { ffi::lua_pop(state, 1) };
+            push_wrapped_error(state, &format!("{}: expected 'protocols' to be a table or nil, got {}", function_name, got_t));
+            return Err(1);
+        }
+        // pop protocols table or nil
+        unsafe { ffi::lua_pop(state, 1) };
+        Ok(protocols)
+    }
 }
\ No newline at end of file
@@ -0,0 +1,310 @@
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use wry::http::{
+    Method, Request, Response, StatusCode,
+    header::{CONTENT_TYPE, HeaderValue},
+};
+
+/// Custom protocol webseal serves app files over, see `app_url`.
+pub const SCHEME: &str = "seal";
+
+/// Gets the URL of `path` in the app served over `SCHEME`.
+///
+/// Custom protocols look like `seal://app/path` on Linux and macOS, but wry maps them to
+/// `http://seal.app/path` on Windows and Android.
+pub fn app_url(path: &str) -> String {
+    if cfg!(any(target_os = "windows", target_os = "android")) {
+        format!("http://{}.app/{}", SCHEME, path)
+    } else {
+        format!("{}://app/{}", SCHEME, path)
+    }
+}
+
+/// A file registered from Luau with the `assets` option or `WebviewIpc:register_asset()`.
+#[derive(Debug)]
+pub struct Asset {
+    pub contents: Vec<u8>,
+    pub mime: HeaderValue,
+}
+impl Asset {
+    /// Guesses the MIME type from the extension of `path` unless `mime` is given.
+    pub fn new(path: &str, contents: Vec<u8>, mime: Option<&str>) -> Result<Self, String> {
+        let mime = match mime {
+            Some(mime) => match HeaderValue::from_str(mime) {
+                Ok(mime) => mime,
+                Err(_) => return Err(format!("invalid MIME type {:?}", mime)),
+            },
+            None => HeaderValue::from_static(mime_type(Path::new(path))),
+        };
+        Ok(Self { contents, mime })
+    }
+}
+
+/// Answers requests to `seal://app/...` for one window.
+///
+/// Registered for every window, since assets can be added after it's built.
+pub struct SealProtocol {
+    /// Canonicalized `root_dir` option.
+    root_dir: Option<PathBuf>,
+    /// Inline html served at the root in place of index.html, so its relative links resolve against the app.
+    pub page: RefCell<Option<String>>,
+    /// Keyed by path without the leading slash; checked before `root_dir`.
+    assets: RefCell<HashMap<String, Asset>>,
+}
+
+impl SealProtocol {
+    pub fn new(root_dir: Option<PathBuf>) -> Self {
+        Self {
+            root_dir,
+            page: RefCell::new(None),
+            assets: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Serves `asset` at `path` (relative to `seal://app/`), replacing whatever was registered there.
+    pub fn register_asset(&self, path: &str, asset: Asset) {
+        self.assets
+            .borrow_mut()
+            .insert(path.trim_start_matches('/').to_string(), asset);
+    }
+
+    pub fn handle(&self, request: &Request<Vec<u8>>) -> Response<Cow<'static, [u8]>> {
+        let mut response = self.respond(request);
+        if request.method() == Method::HEAD {
+            // same status and headers, but HEAD responses never have a body
+            *response.body_mut() = Cow::Borrowed(&[]);
+        }
+        response
+    }
+
+    fn respond(&self, request: &Request<Vec<u8>>) -> Response<Cow<'static, [u8]>> {
+        if request.method() != Method::GET && request.method() != Method::HEAD {
+            return text_response(StatusCode::METHOD_NOT_ALLOWED, "method not allowed");
+        }
+
+        let uri = request.uri();
+        if !matches!(uri.host(), Some("app") | Some("seal.app")) {
+            return text_response(StatusCode::NOT_FOUND, "not found");
+        }
+
+        let Some(path) = percent_decode(uri.path()) else {
+            return text_response(StatusCode::BAD_REQUEST, "malformed path");
+        };
+
+        if path.trim_matches('/').is_empty()
+            && let Some(page) = self.page.borrow().as_ref()
+        {
+            return response(StatusCode::OK, HeaderValue::from_static("text/html; charset=utf-8"), Cow::Owned(page.clone().into_bytes()));
+        }
+
+        // directories map to their index.html, like they do in root_dir
+        let mut key = path.trim_start_matches('/').to_string();
+        if key.is_empty() || key.ends_with('/') {
+            key.push_str("index.html");
+        }
+        if let Some(asset) = self.assets.borrow().get(&key) {
+            return response(StatusCode::OK, asset.mime.clone(), Cow::Owned(asset.contents.clone()));
+        }
+
+        let Some(root_dir) = &self.root_dir else {
+            return text_response(StatusCode::NOT_FOUND, "not found");
+        };
+        let file = match resolve(root_dir, &path) {
+            Ok(Some(file)) => file,
+            Ok(None) => return text_response(StatusCode::NOT_FOUND, "not found"),
+            Err(()) => return text_response(StatusCode::FORBIDDEN, "forbidden"),
+        };
+
+        match std::fs::read(&file) {
+            Ok(contents) => response(StatusCode::OK, HeaderValue::from_static(mime_type(&file)), Cow::Owned(contents)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => text_response(StatusCode::NOT_FOUND, "not found"),
+            Err(err) => {
+                eprintln!("unable to serve {} due to err: {}", file.display(), err);
+                text_response(StatusCode::INTERNAL_SERVER_ERROR, "unable to read file")
+            },
+        }
+    }
+}
+
+/// Maps the url `path` onto a file in `root`; directories map to their index.html.
+///
+/// Returns Ok(None) if there's no such file, and Err if `path` tries to leave `root`.
+fn resolve(root: &Path, path: &str) -> Result<Option<PathBuf>, ()> {
+    let mut resolved = root.to_path_buf();
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => {},
+            ".." => return Err(()),
+            // windows would read these as separators or drive prefixes
+            segment if segment.contains(['\\', ':']) => return Err(()),
+            segment => resolved.push(segment),
+        }
+    }
+    if resolved.is_dir() {
+        resolved.push("index.html");
+    }
+    // symlinks can still point outside of root, so check where the path really ends up
+    let Ok(resolved) = resolved.canonicalize() else {
+        return Ok(None);
+    };
+    if !resolved.starts_with(root) {
+        return Err(());
+    }
+    Ok(Some(resolved))
+}
+
+/// Decodes `%XX` escapes in a url path; None if they're malformed or don't decode to UTF-8.
+fn percent_decode(path: &str) -> Option<String> {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = std::str::from_utf8(bytes.get(i + 1..i + 3)?).ok()?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+/// Guesses the MIME type of `path` from its extension.
+fn mime_type(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "js" | "mjs" => "text/javascript; charset=utf-8",
+        "json" | "map" => "application/json",
+        "txt" => "text/plain; charset=utf-8",
+        "xml" => "application/xml",
+        "wasm" => "application/wasm",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "avif" => "image/avif",
+        "ico" => "image/x-icon",
+        "bmp" => "image/bmp",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "ttf" => "font/ttf",
+        "otf" => "font/otf",
+        "mp3" => "audio/mpeg",
+        "wav" => "audio/wav",
+        "ogg" => "audio/ogg",
+        "mp4" => "video/mp4",
+        "webm" => "video/webm",
+        "pdf" => "application/pdf",
+        _ => "application/octet-stream",
+    }
+}
+
+fn response(status: StatusCode, mime: HeaderValue, body: Cow<'static, [u8]>) -> Response<Cow<'static, [u8]>> {
+    let mut response = Response::new(body);
+    *response.status_mut() = status;
+    response.headers_mut().insert(CONTENT_TYPE, mime);
+    response
+}
+
+pub fn text_response(status: StatusCode, message: &'static str) -> Response<Cow<'static, [u8]>> {
+    response(status, HeaderValue::from_static("text/plain; charset=utf-8"), Cow::Borrowed(message.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh directory under the system temp dir, removed when dropped.
+    struct TempDir(PathBuf);
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("webseal-{}-{}", name, std::process::id()));
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(&dir).unwrap();
+            // canonicalized like root_dir is, since the temp dir can be behind a symlink
+            Self(dir.canonicalize().unwrap())
+        }
+    }
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn resolve_url_path(root: &Path, path: &str) -> Result<Option<PathBuf>, ()> {
+        resolve(root, &percent_decode(path).unwrap())
+    }
+
+    #[test]
+    fn decodes_percent_escapes() {
+        assert_eq!(percent_decode("/a%20b.html").as_deref(), Some("/a b.html"));
+        assert_eq!(percent_decode("/%2e%2e/%2F").as_deref(), Some("/..//"));
+        assert_eq!(percent_decode("/%zz"), None);
+        assert_eq!(percent_decode("/%2"), None);
+        assert_eq!(percent_decode("/%ff"), None);
+    }
+
+    #[test]
+    fn rejects_paths_leaving_root() {
+        let root = TempDir::new("protocol-traversal");
+        std::fs::write(root.0.join("index.html"), "hi").unwrap();
+        assert_eq!(resolve_url_path(&root.0, "/../index.html"), Err(()));
+        assert_eq!(resolve_url_path(&root.0, "/%2e%2e/index.html"), Err(()));
+        assert_eq!(resolve_url_path(&root.0, "/a/..%2F..%2Findex.html"), Err(()));
+        assert_eq!(resolve_url_path(&root.0, "/..\\index.html"), Err(()));
+        assert_eq!(resolve_url_path(&root.0, "/a%5C..%5Cindex.html"), Err(()));
+        assert_eq!(resolve_url_path(&root.0, "/C:/index.html"), Err(()));
+    }
+
+    #[test]
+    fn resolves_files_and_directories() {
+        let root = TempDir::new("protocol-resolve");
+        std::fs::create_dir(root.0.join("docs")).unwrap();
+        std::fs::write(root.0.join("index.html"), "root").unwrap();
+        std::fs::write(root.0.join("docs").join("index.html"), "docs").unwrap();
+        std::fs::write(root.0.join("docs").join("a b.css"), "css").unwrap();
+        assert_eq!(resolve_url_path(&root.0, "/"), Ok(Some(root.0.join("index.html"))));
+        assert_eq!(resolve_url_path(&root.0, "/docs/"), Ok(Some(root.0.join("docs").join("index.html"))));
+        assert_eq!(resolve_url_path(&root.0, "/docs/./a%20b.css"), Ok(Some(root.0.join("docs").join("a b.css"))));
+        assert_eq!(resolve_url_path(&root.0, "/missing.js"), Ok(None));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn rejects_symlinks_leaving_root() {
+        let outside = TempDir::new("protocol-outside");
+        std::fs::write(outside.0.join("secret.txt"), "secret").unwrap();
+        let root = TempDir::new("protocol-symlink");
+        std::os::unix::fs::symlink(outside.0.join("secret.txt"), root.0.join("secret.txt")).unwrap();
+        std::os::unix::fs::symlink(&outside.0, root.0.join("outside")).unwrap();
+        assert_eq!(resolve_url_path(&root.0, "/secret.txt"), Err(()));
+        assert_eq!(resolve_url_path(&root.0, "/outside/secret.txt"), Err(()));
+    }
+
+    #[test]
+    fn head_requests_have_no_body() {
+        let protocol = SealProtocol::new(None);
+        protocol.register_asset("style.css", Asset::new("style.css", b"body {}".to_vec(), None).unwrap());
+        let request = |method| Request::builder().method(method).uri("seal://app/style.css").body(Vec::new()).unwrap();
+
+        let response = protocol.handle(&request(Method::GET));
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.body().as_ref(), b"body {}");
+
+        let response = protocol.handle(&request(Method::HEAD));
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[CONTENT_TYPE], "text/css; charset=utf-8");
+        assert!(response.body().is_empty());
+    }
+}
@@ -0,0 +1,122 @@
+use seal::ffi;
+use tao::{monitor::MonitorHandle, window::Window};
+
+/// Window state that Luau can read with getters like `WebviewIpc:position()`.
+///
+/// Answered on the UI thread, see `ToWindow::Query`.
+#[derive(Debug, Clone, Copy)]
+pub enum WindowQuery {
+    Position,
+    OuterSize,
+    IsMaximized,
+    IsMinimized,
+    IsFocused,
+    IsVisible,
+    IsFullscreen,
+    ScaleFactor,
+    Title,
+    CurrentMonitor,
+}
+
+#[derive(Debug)]
+pub enum QueryReply {
+    Vector(f32, f32),
+    Boolean(bool),
+    Number(f64),
+    String(String),
+    Monitor(MonitorInfo),
+    /// The platform can't answer the query (like window position on Wayland).
+    Nil,
+}
+
+/// Everything Luau gets to know about a monitor.
+#[derive(Debug, Clone)]
+pub struct MonitorInfo {
+    pub name: Option<String>,
+    /// Top-left corner of the monitor in physical pixels.
+    pub position: (f32, f32),
+    /// Size of the monitor in physical pixels.
+    pub size: (f32, f32),
+    pub scale_factor: f64,
+}
+impl MonitorInfo {
+    pub fn from_handle(monitor: &MonitorHandle) -> Self {
+        let position = monitor.position();
+        let size = monitor.size();
+        Self {
+            name: monitor.name(),
+            position: (position.x as f32, position.y as f32),
+            size: (size.width as f32, size.height as f32),
+            scale_factor: monitor.scale_factor(),
+        }
+    }
+    /// Pushes `{ name: string?, position: vector, size: vector, scale_factor: number }` onto the Luau stack.
+    ///
+    /// # Safety
+    /// - state must be a non-null pointer to a lua_State
+    pub unsafe fn push(&self, state: *mut ffi::lua_State) {
+        unsafe {
+            ffi::luaL_checkstack(state, 2, c"can't push monitor".as_ptr());
+            ffi::lua_createtable(state, 0, 4);
+
+            if let Some(name) = &self.name {
+                ffi::lua_pushlstring(state, name.as_ptr() as *const _, name.len());
+                ffi::lua_setfield(state, -2, c"name".as_ptr());
+            }
+
+            ffi::lua_pushvector(state, self.position.0, self.position.1, 0.0);
+            ffi::lua_setfield(state, -2, c"position".as_ptr());
+
+            ffi::lua_pushvector(state, self.size.0, self.size.1, 0.0);
+            ffi::lua_setfield(state, -2, c"size".as_ptr());
+
+            ffi::lua_pushnumber(state, self.scale_factor);
+            ffi::lua_setfield(state, -2, c"scale_factor".as_ptr());
+        }
+    }
+}
+
+impl WindowQuery {
+    pub fn answer(self, window: &Window) -> QueryReply {
+        match self {
+            WindowQuery::Position => match window.outer_position() {
+                Ok(position) => QueryReply::Vector(position.x as f32, position.y as f32),
+                Err(_) => QueryReply::Nil,
+            },
+            WindowQuery::OuterSize => {
+                let size = window.outer_size();
+                QueryReply::Vector(size.width as f32, size.height as f32)
+            },
+            WindowQuery::IsMaximized => QueryReply::Boolean(window.is_maximized()),
+            WindowQuery::IsMinimized => QueryReply::Boolean(window.is_minimized()),
+            WindowQuery::IsFocused => QueryReply::Boolean(window.is_focused()),
+            WindowQuery::IsVisible => QueryReply::Boolean(window.is_visible()),
+            WindowQuery::IsFullscreen => QueryReply::Boolean(window.fullscreen().is_some()),
+            WindowQuery::ScaleFactor => QueryReply::Number(window.scale_factor()),
+            WindowQuery::Title => QueryReply::String(window.title()),
+            WindowQuery::CurrentMonitor => match window.current_monitor() {
+                Some(monitor) => QueryReply::Monitor(MonitorInfo::from_handle(&monitor)),
+                None => QueryReply::Nil,
+            },
+        }
+    }
+}
+
+impl QueryReply {
+    /// Pushes the reply onto the Luau stack as its Luau equivalent.
+    ///
+    /// # Safety
+    /// - state must be a non-null pointer to a lua_State
+    pub unsafe fn push(&self, state: *mut ffi::lua_State) {
+        unsafe {
+            match self {
+                QueryReply::Vector(x, y) => ffi::lua_pushvector(state, *x, *y, 0.0),
+                QueryReply::Boolean(b) => ffi::lua_pushboolean(state, *b as i32),
+                QueryReply::Number(n) => ffi::lua_pushnumber(state, *n),
+                QueryReply::String(s) => ffi::lua_pushlstring(state, s.as_ptr() as *const _, s.len()),
+                QueryReply::Monitor(monitor) => monitor.push(state),
+                QueryReply::Nil => ffi::lua_pushnil(state),
+            }
+        }
+    }
+}
@@ -0,0 +1,205 @@
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use crossbeam_channel::{Receiver, RecvTimeoutError, SendError, Sender};
+use tao::{
+    event::Event,
+    event_loop::{ControlFlow, EventLoopBuilder, EventLoopProxy, EventLoopWindowTarget},
+    platform::{run_return::EventLoopExtRunReturn, unix::EventLoopBuilderExtUnix},
+    window::WindowId,
+};
+
+use crate::options::WebviewOptions;
+use crate::query::MonitorInfo;
+use crate::window::{UserEvent, WebviewWindow};
+use crate::{ToLuau, ToWindow};
+
+/// How long quick requests like `webseal.monitors()` wait for the UI thread before giving up.
+///
+/// `webseal.create()` waits without one, since the first webview can take a while to start.
+pub const UI_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Requests handled by the UI thread, sent as user events to its event loop.
+pub enum UiRequest {
+    Monitors(Sender<Vec<MonitorInfo>>),
+    PrimaryMonitor(Sender<Option<MonitorInfo>>),
+    /// Opens a new window that talks to Luau over the given channels,
+    /// replying once it's open or with why it couldn't be.
+    CreateWindow(Box<WebviewOptions>, Sender<ToLuau>, Receiver<ToWindow>, Sender<Result<(), String>>),
+    /// Input from the page in the window with this id.
+    Webview(WindowId, UserEvent),
+    /// Luau queued commands for a window, see `WindowSender`.
+    Commands,
+}
+
+/// Queues commands for a window on the UI thread and wakes its event loop up to handle them.
+pub struct WindowSender {
+    sender: Sender<ToWindow>,
+    proxy: &'static EventLoopProxy<UiRequest>,
+}
+impl WindowSender {
+    pub fn new(sender: Sender<ToWindow>) -> Result<Self, String> {
+        Ok(Self {
+            sender,
+            proxy: proxy()?,
+        })
+    }
+    /// Fails if the window has closed.
+    pub fn send(&self, message: ToWindow) -> Result<(), SendError<ToWindow>> {
+        self.sender.send(message)?;
+        // this only fails if the UI thread exited, which takes the window with it anyway
+        let _ = self.proxy.send_event(UiRequest::Commands);
+        Ok(())
+    }
+}
+
+/// Proxy to the event loop on the UI thread, or why the thread couldn't start.
+static UI_THREAD: OnceLock<Result<EventLoopProxy<UiRequest>, String>> = OnceLock::new();
+
+/// Gets a proxy to the UI thread's event loop, starting the thread the first time it's needed.
+///
+/// The UI thread owns every window (GTK doesn't like more than one event loop), and lives
+/// for the rest of the program so it can also answer queries that don't belong to any window
+/// (like listing monitors).
+fn proxy() -> Result<&'static EventLoopProxy<UiRequest>, String> {
+    let proxy = UI_THREAD.get_or_init(|| {
+        let (proxy_tx, proxy_rx) = crossbeam_channel::bounded(1);
+        let spawned = std::thread::Builder::new()
+            .name(String::from("webseal-ui"))
+            .spawn(move || {
+                // tao panics if it can't initialize (like when there's no display), so catch that
+                // and pass the message along instead of leaving luau to guess what happened
+                let built = std::panic::catch_unwind(|| {
+                    EventLoopBuilder::<UiRequest>
+                        ::with_user_event()
+                        .with_any_thread(true)
+                        .build()
+                });
+                let mut event_loop = match built {
+                    Ok(event_loop) => event_loop,
+                    Err(panic) => {
+                        let message = panic
+                            .downcast_ref::<&str>()
+                            .map(|message| message.to_string())
+                            .or_else(|| panic.downcast_ref::<String>().cloned())
+                            .unwrap_or_else(|| String::from("unknown error"));
+                        let _ = proxy_tx.send(Err(format!("unable to start the UI thread due to err: {}", message)));
+                        return;
+                    }
+                };
+                let proxy = event_loop.create_proxy();
+                let _ = proxy_tx.send(Ok(proxy.clone()));
+                let mut windows: HashMap<WindowId, WebviewWindow> = HashMap::new();
+                // `run` exits the whole process once the loop stops, so use run_return to only
+                // ever end this thread; closing a window just drops it from the map
+                event_loop.run_return(|event, target, control_flow| {
+                    // requests and commands arrive as user events, which wake the loop up,
+                    // so there's nothing to do in between
+                    *control_flow = ControlFlow::Wait;
+
+                    match event {
+                        Event::WindowEvent { window_id, event, .. } => {
+                            let keep_open = windows
+                                .get(&window_id)
+                                .is_none_or(|window| window.handle_window_event(event));
+                            if !keep_open {
+                                windows.remove(&window_id);
+                            }
+                        },
+                        Event::UserEvent(UiRequest::Webview(window_id, event)) => {
+                            let keep_open = windows
+                                .get_mut(&window_id)
+                                .is_none_or(|window| window.handle_user_event(event));
+                            if !keep_open {
+                                windows.remove(&window_id);
+                            }
+                        },
+                        Event::UserEvent(UiRequest::Commands) => {
+                            // one wakeup can stand in for many commands, so drain them all
+                            windows.retain(|_, window| window.handle_commands());
+                        },
+                        Event::UserEvent(request) => handle(request, target, &proxy, &mut windows),
+                        _ => (),
+                    }
+                });
+            });
+        if let Err(err) = spawned {
+            return Err(format!("unable to spawn the UI thread due to err: {}", err));
+        }
+        match proxy_rx.recv() {
+            Ok(proxy) => proxy,
+            Err(_) => Err(String::from("the UI thread failed to start")),
+        }
+    });
+    proxy.as_ref().map_err(|err| err.clone())
+}
+
+fn handle(
+    request: UiRequest,
+    target: &EventLoopWindowTarget<UiRequest>,
+    proxy: &EventLoopProxy<UiRequest>,
+    windows: &mut HashMap<WindowId, WebviewWindow>,
+) {
+    match request {
+        UiRequest::Monitors(reply) => {
+            let monitors = target
+                .available_monitors()
+                .map(|monitor| MonitorInfo::from_handle(&monitor))
+                .collect();
+            let _ = reply.send(monitors);
+        },
+        UiRequest::PrimaryMonitor(reply) => {
+            let monitor = target
+                .primary_monitor()
+                .map(|monitor| MonitorInfo::from_handle(&monitor));
+            let _ = reply.send(monitor);
+        },
+        UiRequest::CreateWindow(options, sender, receiver, reply) => {
+            match WebviewWindow::build(*options, sender, receiver, target, proxy) {
+                Ok(window) => {
+                    let window_id = window.id();
+                    windows.insert(window_id, window);
+                    if reply.send(Ok(())).is_err() {
+                        // luau gave up waiting, so nothing can ever use the window
+                        windows.remove(&window_id);
+                    }
+                },
+                Err(err) => {
+                    let _ = reply.send(Err(err));
+                },
+            }
+        },
+        // routed to windows before getting here
+        UiRequest::Webview(..) | UiRequest::Commands => {},
+    }
+}
+
+/// Sends `request` to the UI thread without waiting for anything back.
+pub fn send(request: UiRequest) -> Result<(), String> {
+    proxy()?
+        .send_event(request)
+        .map_err(|_| String::from("the UI thread has exited"))
+}
+
+/// Hands `send` a fresh reply channel, then waits for the reply (forever if `timeout` is None).
+///
+/// A request that couldn't be sent drops its reply sender, and so does anything that gives up on one
+/// without answering, so either way the wait ends with `RecvTimeoutError::Disconnected` instead of hanging.
+pub fn send_and_wait<T>(send: impl FnOnce(Sender<T>), timeout: Option<Duration>) -> Result<T, RecvTimeoutError> {
+    let (reply_tx, reply_rx) = crossbeam_channel::bounded(1);
+    send(reply_tx);
+    match timeout {
+        Some(timeout) => reply_rx.recv_timeout(timeout),
+        None => reply_rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+    }
+}
+
+/// Asks the UI thread for something and waits for the answer, see `send_and_wait`.
+pub fn request<T>(make_request: impl FnOnce(Sender<T>) -> UiRequest, timeout: Option<Duration>) -> Result<T, String> {
+    let mut sent = Ok(());
+    let reply = send_and_wait(|reply_tx| sent = send(make_request(reply_tx)), timeout);
+    // why the request couldn't be sent says more than the reply channel disconnecting
+    sent?;
+    reply.map_err(|err| format!("no response from the UI thread: {}", err))
+}
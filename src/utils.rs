@@ -1,6 +1,7 @@
 use std::ffi::{CStr, CString};
+use std::path::{Path, PathBuf};
 
-use bstr::BString;
+use bstr::{BString, ByteSlice};
 use libc::c_int;
 use crate::ffi;
 
@@ -93,6 +94,63 @@ impl BStringFromPtr for BString {
     }
 }
 
+/// Copies the bytes of the string or buffer at `idx` on the Luau stack; None if it's something else.
+///
+/// # Safety
+/// - state must be a non-null pointer to a lua_State
+/// - `idx` must be on the luau stack
+pub unsafe fn bytes_at(state: *mut ffi::lua_State, idx: c_int) -> Option<Vec<u8>> {
+    let mut len = 0;
+    let ptr = unsafe {
+        match ffi::lua_type(state, idx) {
+            ffi::LUA_TSTRING => ffi::lua_tolstring(state, idx, &mut len) as *const u8,
+            ffi::LUA_TBUFFER => ffi::lua_tobuffer(state, idx, &mut len) as *const u8,
+            _ => return None,
+        }
+    };
+    if ptr.is_null() || len == 0 {
+        return Some(Vec::new());
+    }
+    // SAFETY: luau guarantees ptr points to len bytes for strings and buffers
+    Some(unsafe { std::slice::from_raw_parts(ptr, len) }.to_vec())
+}
+
+/// Gets the directory of the nearest Luau script on the call stack, from its chunk name.
+///
+/// Walks up past C frames (like seal's wrapper around the current C function) and chunks that weren't
+/// loaded from files, and errors if no script on the stack was.
+///
+/// # Safety
+/// - state must be a non-null pointer to a lua_State
+pub unsafe fn script_dir(state: *mut ffi::lua_State) -> Result<PathBuf, String> {
+    // level 0 is the current C function
+    let mut level = 1;
+    loop {
+        // SAFETY: lua_Debug is a plain C struct, so all zeroes is a valid (empty) one
+        let mut ar: ffi::lua_Debug = unsafe { std::mem::zeroed() };
+        if unsafe { ffi::lua_getinfo(state, level, c"s".as_ptr(), &mut ar) } == 0 {
+            return Err(String::from("no script loaded from a file is on the call stack"));
+        }
+        level += 1;
+        if ar.source.is_null() || (!ar.what.is_null() && unsafe { CStr::from_ptr(ar.what) } == c"C") {
+            continue;
+        }
+        let source = unsafe { BString::clone_from_ptr(ar.source) }.to_str_lossy().to_string();
+        // chunks loaded from files are conventionally named "@path"
+        let Some(path) = source.strip_prefix('@') else {
+            continue;
+        };
+        let path = Path::new(path);
+        if !path.is_file() {
+            return Err(format!("can't find the calling script {}", path.display()));
+        }
+        return match path.parent() {
+            Some(dir) => Ok(dir.to_path_buf()),
+            None => Err(format!("can't find the directory of the calling script {}", path.display())),
+        };
+    }
+}
+
 #[allow(unused, reason = "only needed for debugging")]
 /// # Safety
 /// - state must be a non-null pointer to a lua_State
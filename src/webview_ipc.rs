@@ -1,23 +1,141 @@
-use std::ffi::{CString, c_int};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::ffi::{CStr, c_int, c_void};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
 use bstr::{BString, ByteSlice};
-use crossbeam_channel::TryRecvError;
+use crossbeam_channel::{RecvTimeoutError, Sender, TryRecvError};
 use seal::{ffi, push_wrapped_error};
+use serde_json::Value;
 
-use crate::{ToLuau, ToWindow};
+use crate::{ToLuau, ToWindow, json};
+use crate::query::WindowQuery;
+use crate::command::WindowCommand;
+use crate::luau_protocol;
+use crate::protocol::Asset;
+use crate::ui::{self, WindowSender};
 
 use crate::utils::{self, BStringFromPtr};
 
 pub const WEBVIEW_IPC_TAG: c_int = 13;
 
+/// How long queries like `size()` wait for the UI thread before giving up.
+const QUERY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Pushes a new event table `{ kind = kind }` onto the Luau stack.
+///
+/// # Safety
+/// - state must be a non-null pointer to a lua_State with at least 2 free stack slots
+unsafe fn push_event(state: *mut ffi::lua_State, kind: &CStr) {
+    unsafe {
+        ffi::lua_createtable(state, 0, 2);
+        ffi::lua_pushstring(state, kind.as_ptr());
+        ffi::lua_setfield(state, -2, c"kind".as_ptr());
+    }
+}
+
+/// How long `WebviewIpc::next_message` should wait for a message.
+#[derive(Clone, Copy)]
+enum Wait {
+    No,
+    Forever,
+    Until(Instant),
+}
+
 pub struct WebviewIpc {
-    pub sender: crossbeam_channel::Sender<ToWindow>,
+    pub sender: WindowSender,
     pub receiver: crossbeam_channel::Receiver<ToLuau>,
+    /// Registry refs to Luau functions bound with `WebviewIpc:bind`, callable from JS with `seal.invoke`.
+    pub bindings: RefCell<HashMap<String, c_int>>,
+    /// Registry refs to the Luau functions answering requests to each scheme in the `protocols` option.
+    protocols: HashMap<String, c_int>,
+    /// How many bound functions and protocol handlers are running right now;
+    /// `destroy()` can't free the WebviewIpc out from under them.
+    binding_depth: Cell<u32>,
+}
+impl Drop for WebviewIpc {
+    fn drop(&mut self) {
+        // fails if the window already closed, which is fine
+        let _ = self.sender.send(ToWindow::Destroy);
+    }
+}
+
+/// Registry refs of garbage collected handles, waiting for `release_collected_refs`.
+static COLLECTED_REFS: Mutex<Vec<c_int>> = Mutex::new(Vec::new());
+
+/// Destructor for WebviewIpc userdata, called when Luau garbage collects a handle that wasn't destroyed.
+///
+/// Bound functions and protocol handlers can't be released here because the Luau API isn't safe to use mid-collection,
+/// so their refs are queued for `release_collected_refs` instead.
+///
+/// # Safety
+/// - ud must point to userdata tagged `WEBVIEW_IPC_TAG`
+pub unsafe extern "C-unwind" fn destructor(_state: *mut ffi::lua_State, ud: *mut c_void) {
+    unsafe {
+        // null if the WebviewIpc was already destroyed
+        let ipc_ptr: *mut WebviewIpc = *(ud as *mut *mut WebviewIpc);
+        if !ipc_ptr.is_null() {
+            let ipc = Box::from_raw(ipc_ptr);
+            let refs = ipc.bindings.take().into_values().chain(ipc.protocols.values().copied());
+            // a poisoned lock only means a panic elsewhere, the refs are still worth releasing
+            COLLECTED_REFS.lock().unwrap_or_else(|err| err.into_inner()).extend(refs);
+            drop(ipc);
+        }
+    }
+}
+
+/// Releases the refs queued by `destructor`; called on the way into the library, where the Luau API is safe again.
+///
+/// # Safety
+/// - state must be a non-null pointer to a lua_State that isn't in the middle of a collection
+pub unsafe fn release_collected_refs(state: *mut ffi::lua_State) {
+    let refs = std::mem::take(&mut *COLLECTED_REFS.lock().unwrap_or_else(|err| err.into_inner()));
+    for function_ref in refs {
+        unsafe { ffi::lua_unref(state, function_ref) };
+    }
 }
 impl WebviewIpc {
+    pub fn new(sender: WindowSender, receiver: crossbeam_channel::Receiver<ToLuau>, protocols: HashMap<String, c_int>) -> Self {
+        Self {
+            sender,
+            receiver,
+            bindings: RefCell::new(HashMap::new()),
+            protocols,
+            binding_depth: Cell::new(0),
+        }
+    }
+    /// Gets the next message for `try_read`/`read`.
+    ///
+    /// Returns `RecvTimeoutError::Timeout` if nothing arrives in time (immediately for `Wait::No`).
+    fn next_message(&self, wait: Wait) -> Result<ToLuau, RecvTimeoutError> {
+        match wait {
+            Wait::No => self.receiver.try_recv().map_err(|err| match err {
+                TryRecvError::Empty => RecvTimeoutError::Timeout,
+                TryRecvError::Disconnected => RecvTimeoutError::Disconnected,
+            }),
+            Wait::Forever => self.receiver.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            Wait::Until(deadline) => self.receiver.recv_deadline(deadline),
+        }
+    }
+    /// Sends the request built by `make_request` to the window and waits for its reply, see `ui::send_and_wait`.
+    ///
+    /// Replies never go through `receiver`, so queries can't steal or drop messages meant for `read`.
+    fn request<T>(&self, make_request: impl FnOnce(Sender<T>) -> ToWindow, timeout: Option<Duration>) -> Result<T, String> {
+        let reply = ui::send_and_wait(|reply_tx| {
+            // fails once the window has closed, which disconnects the reply channel too
+            let _ = self.sender.send(make_request(reply_tx));
+        }, timeout);
+        // the UI thread drops the reply sender without answering if the window closes first
+        reply.map_err(|err| match err {
+            RecvTimeoutError::Timeout => String::from("timed out waiting for the window to respond"),
+            RecvTimeoutError::Disconnected => String::from("the window has been closed"),
+        })
+    }
     /// Gets the &WebviewIpc from `idx` on the Luau stack, popping it.
     /// 
-    /// Pushes a wrapped error message onto the Luau stack if unable to get the WebviewIpc for whatever reason.
+    /// Pushes a wrapped error message onto the Luau stack if unable to get the WebviewIpc for whatever reason,
+    /// including if it's been destroyed.
     /// 
     /// Removes the WebviewIpc userdata from the Luau stack if successful
     /// 
@@ -25,6 +143,38 @@ impl WebviewIpc {
     /// - make sure `idx` is the CORRECT idx
     /// - make sure `idx` actually exists on the stack (gettop)
     unsafe fn get(state: *mut ffi::lua_State, idx: c_int, function_name: &'static str) -> Result<&'static Self, c_int>{
+        let ud_ptr = unsafe { Self::get_userdata(state, idx, function_name)? };
+
+        // SAFETY: 
+        // - The UserData pointer was created from Box<WebviewIpc>
+        // - The UserData pointer tag was checked to be of type WebViewIpc
+        // - The UserData pointer was checked to be non-null
+        // - The WebviewIpc is owned by Rust and was leaked by Box::into_raw
+        // - destroy() and the destructor null out the inner pointer when they free the WebviewIpc
+        // - The caller is responsible for ensuring the WebviewIpc is leaked or otherwise still alive
+        unsafe { 
+            // first deref the ud pointer to get a pointer to WebviewIpc
+            let ipc_ptr: *mut WebviewIpc = *ud_ptr;
+            if ipc_ptr.is_null() {
+                push_wrapped_error(state, &format!("{}: the window has been destroyed", function_name));
+                return Err(1);
+            }
+            // next, deref the ipc ptr to get the actual WebviewIpc
+            Ok(&*ipc_ptr)
+        }
+    }
+    /// Gets the pointer to the `*mut WebviewIpc` inside the userdata at `idx` on the Luau stack, popping it.
+    /// The inner pointer is null if the WebviewIpc has been destroyed.
+    ///
+    /// Pushes a wrapped error message onto the Luau stack if `idx` isn't WebviewIpc userdata.
+    ///
+    /// # Safety
+    /// - make sure `idx` is the CORRECT idx
+    /// - make sure `idx` actually exists on the stack (gettop)
+    unsafe fn get_userdata(state: *mut ffi::lua_State, idx: c_int, function_name: &'static str) -> Result<*mut *mut WebviewIpc, c_int> {
+        // every method comes through here, so it's a good time to catch up on garbage collected handles
+        unsafe { release_collected_refs(state) };
+
         let ud_ptr = unsafe {
             if ffi::lua_type(state, idx) == ffi::LUA_TUSERDATA {
                 // SAFETY: ptr is null if ud is not a WebviewIpc; we check that the ptr is non-null later
@@ -48,22 +198,214 @@ impl WebviewIpc {
             push_wrapped_error(state, "self is the wrong kind of userdata (expected WebviewIpc tag 13)");
             return Err(1);
         }
-        
-        // SAFETY: 
-        // - The UserData pointer was created from Box<WebviewIpc>
-        // - The UserData pointer tag was checked to be of type WebViewIpc
-        // - The UserData pointer was checked to be non-null
-        // - The WebviewIpc is owned by Rust and was leaked by Box::into_raw
-        // - The caller is responsible for ensuring the WebviewIpc is leaked or otherwise still alive
-        unsafe { 
-            // first deref the ud pointer to get a pointer to WebviewIpc
-            let ipc_ptr: *mut WebviewIpc = *(ud_ptr as *mut *mut WebviewIpc);
-            if ipc_ptr.is_null() {
-                push_wrapped_error(state, "inner pointer to *mut WebviewIpc inside the userdata holding *mut *mut WebviewIpc is null");
+
+        Ok(ud_ptr as *mut *mut WebviewIpc)
+    }
+    /// Calls the Luau function bound to `name` with `args` and sends its result back to the page,
+    /// resolving (or rejecting, if it errors) the promise returned by `seal.invoke`.
+    ///
+    /// # Safety
+    /// - state must be a non-null pointer to a lua_State
+    unsafe fn answer_invoke(&self, state: *mut ffi::lua_State, id: u64, name: &str, args: Value) {
+        let result = unsafe { self.call_binding(state, name, args) };
+        if let Err(err) = self.sender.send(ToWindow::InvokeResult(id, result)) {
+            eprintln!("unable to send invoke result to page due to err: {}", err);
+        }
+    }
+    /// Calls the Luau function bound to `name`, returning its JSON-serialized return value
+    /// or its error message; leaves the stack balanced.
+    ///
+    /// # Safety
+    /// - state must be a non-null pointer to a lua_State
+    unsafe fn call_binding(&self, state: *mut ffi::lua_State, name: &str, args: Value) -> Result<String, String> {
+        // copy the ref out so the binding can rebind itself without a double borrow
+        let Some(function_ref) = self.bindings.borrow().get(name).copied() else {
+            return Err(format!("no Luau function is bound to '{}'", name));
+        };
+
+        // seal.invoke(name, ...args) always sends args as an array
+        let args = match args {
+            Value::Array(args) => args,
+            other => vec![other],
+        };
+
+        unsafe {
+            ffi::luaL_checkstack(state, args.len() as c_int + 2, c"too many arguments passed to bound function".as_ptr());
+            ffi::lua_rawgeti(state, ffi::LUA_REGISTRYINDEX, function_ref);
+            for arg in &args {
+                json::push_value(state, arg);
+            }
+
+            self.binding_depth.set(self.binding_depth.get() + 1);
+            let status = ffi::lua_pcall(state, args.len() as c_int, 1, 0);
+            self.binding_depth.set(self.binding_depth.get() - 1);
+
+            if status != ffi::LUA_OK {
+                // tolstring respects __tostring, so seal's wrapped errors keep their messages
+                let ptr = ffi::luaL_tolstring(state, -1, std::ptr::null_mut());
+                let message = BString::clone_from_ptr(ptr).to_str_lossy().to_string();
+                // pop error and its tostring
+                ffi::lua_pop(state, 2);
+                return Err(message);
+            }
+
+            let result = json::value_from_stack(state, -1);
+            ffi::lua_pop(state, 1);
+            result
+                .map(|value| value.to_string())
+                .map_err(|err| format!("unable to serialize return value of '{}': {}", name, err))
+        }
+    }
+    /// Calls the Luau handler for `scheme` with `request` and sends the response back to the page.
+    ///
+    /// Errors in the handler (or a malformed response) become a 500 with the error message as the body.
+    ///
+    /// # Safety
+    /// - state must be a non-null pointer to a lua_State
+    unsafe fn answer_protocol(&self, state: *mut ffi::lua_State, id: u64, scheme: &str, request: Box<wry::http::Request<Vec<u8>>>) {
+        let response = match unsafe { self.call_protocol(state, scheme, &request) } {
+            Ok(response) => response,
+            Err(message) => {
+                eprintln!("{} handler failed to answer {}: {}", scheme, request.uri(), message);
+                luau_protocol::error_response(wry::http::StatusCode::INTERNAL_SERVER_ERROR, message)
+            },
+        };
+        if let Err(err) = self.sender.send(ToWindow::ProtocolResponse(id, Box::new(response))) {
+            eprintln!("unable to send protocol response to page due to err: {}", err);
+        }
+    }
+    /// Calls the Luau handler for `scheme`, returning the response it built or its error message;
+    /// leaves the stack balanced.
+    ///
+    /// # Safety
+    /// - state must be a non-null pointer to a lua_State
+    unsafe fn call_protocol(&self, state: *mut ffi::lua_State, scheme: &str, request: &wry::http::Request<Vec<u8>>) -> Result<wry::http::Response<Vec<u8>>, String> {
+        let Some(function_ref) = self.protocols.get(scheme).copied() else {
+            return Err(format!("no Luau handler for the {:?} scheme", scheme));
+        };
+
+        unsafe {
+            ffi::luaL_checkstack(state, 2, c"can't call protocol handler".as_ptr());
+            ffi::lua_rawgeti(state, ffi::LUA_REGISTRYINDEX, function_ref);
+            luau_protocol::push_request(state, request);
+
+            self.binding_depth.set(self.binding_depth.get() + 1);
+            let status = ffi::lua_pcall(state, 1, 1, 0);
+            self.binding_depth.set(self.binding_depth.get() - 1);
+
+            if status != ffi::LUA_OK {
+                let ptr = ffi::luaL_tolstring(state, -1, std::ptr::null_mut());
+                let message = BString::clone_from_ptr(ptr).to_str_lossy().to_string();
+                // pop error and its tostring
+                ffi::lua_pop(state, 2);
+                return Err(message);
+            }
+
+            let response = luau_protocol::response_from_stack(state);
+            ffi::lua_pop(state, 1);
+            response
+        }
+    }
+    /// Gets an optional timeout in seconds from `idx` on the Luau stack, popping it.
+    ///
+    /// Pushes a wrapped error message onto the Luau stack if the timeout isn't nil or a non-negative number.
+    /// Timeouts too long to represent come back as None, the same as nil.
+    ///
+    /// # Safety
+    /// - make sure `idx` is the CORRECT idx
+    unsafe fn get_timeout(state: *mut ffi::lua_State, idx: c_int, function_name: &'static str) -> Result<Option<Duration>, c_int> {
+        let timeout_type = unsafe { ffi::lua_type(state, idx) };
+        let timeout = if timeout_type == ffi::LUA_TNUMBER {
+            let seconds = unsafe { ffi::lua_tonumber(state, idx) };
+            if !seconds.is_finite() || seconds < 0.0 {
+                unsafe { ffi::lua_remove(state, idx) };
+                push_wrapped_error(state, &format!("{}: expected 'timeout' to be a non-negative number of seconds, got {}", function_name, seconds));
                 return Err(1);
             }
-            // next, deref the ipc ptr to get the actual WebviewIpc
-            Ok(&*ipc_ptr)
+            // only fails for timeouts longer than a Duration can hold, which might as well be forever
+            Duration::try_from_secs_f64(seconds).ok()
+        } else if timeout_type == ffi::LUA_TNIL {
+            None
+        } else {
+            let got_t = unsafe { utils::type_of(state, idx) };
+            unsafe { ffi::lua_remove(state, idx) };
+            push_wrapped_error(state, &format!("{}: expected 'timeout' to be a number or nil, got {}", function_name, got_t));
+            return Err(1);
+        };
+        unsafe { ffi::lua_remove(state, idx) };
+        Ok(timeout)
+    }
+    /// Gets a string argument named `arg_name` from `idx` on the Luau stack, popping it.
+    ///
+    /// Pushes a wrapped error message onto the Luau stack if the argument is missing or not a string.
+    ///
+    /// # Safety
+    /// - make sure `idx` is the CORRECT idx
+    unsafe fn get_string(state: *mut ffi::lua_State, idx: c_int, function_name: &'static str, arg_name: &'static str) -> Result<String, c_int> {
+        unsafe {
+            if ffi::lua_type(state, idx) == ffi::LUA_TSTRING {
+                let ptr = ffi::lua_tostring(state, idx);
+                let s = BString::clone_from_ptr(ptr).to_str_lossy().to_string();
+                ffi::lua_remove(state, idx);
+                Ok(s)
+            } else if ffi::lua_isnone(state, idx) == 1 {
+                push_wrapped_error(state, &format!("{}: called without required argument {}", function_name, arg_name));
+                Err(1)
+            } else {
+                let got_t = utils::type_of(state, idx);
+                // pop whatever we got to balance stack
+                ffi::lua_remove(state, idx);
+                push_wrapped_error(state, &format!("{}: expected '{}' to be a string, got {}", function_name, arg_name, got_t));
+                Err(1)
+            }
+        }
+    }
+    /// Gets a boolean argument named `arg_name` from `idx` on the Luau stack, popping it.
+    ///
+    /// Pushes a wrapped error message onto the Luau stack if the argument is missing or not a boolean.
+    ///
+    /// # Safety
+    /// - make sure `idx` is the CORRECT idx
+    unsafe fn get_bool(state: *mut ffi::lua_State, idx: c_int, function_name: &'static str, arg_name: &'static str) -> Result<bool, c_int> {
+        unsafe {
+            if ffi::lua_type(state, idx) == ffi::LUA_TBOOLEAN {
+                let b = ffi::lua_toboolean(state, idx) != 0;
+                ffi::lua_remove(state, idx);
+                Ok(b)
+            } else {
+                let got_t = utils::type_of(state, idx);
+                ffi::lua_remove(state, idx);
+                push_wrapped_error(state, &format!("{}: expected '{}' to be a boolean, got {}", function_name, arg_name, got_t));
+                Err(1)
+            }
+        }
+    }
+    /// Gets the x and y of a vector argument named `arg_name` from `idx` on the Luau stack, popping it.
+    /// If `optional`, nil is accepted and returned as None.
+    ///
+    /// Pushes a wrapped error message onto the Luau stack if the argument isn't a vector (or nil when `optional`).
+    ///
+    /// # Safety
+    /// - make sure `idx` is the CORRECT idx
+    unsafe fn get_vector(state: *mut ffi::lua_State, idx: c_int, function_name: &'static str, arg_name: &'static str, optional: bool) -> Result<Option<(f32, f32)>, c_int> {
+        unsafe {
+            let t = ffi::lua_type(state, idx);
+            if t == ffi::LUA_TVECTOR {
+                // SAFETY: lua_tovector points to x, which is followed by y and z
+                let x_ptr = ffi::lua_tovector(state, idx);
+                let xy = (*x_ptr, *x_ptr.add(1));
+                ffi::lua_remove(state, idx);
+                Ok(Some(xy))
+            } else if optional && t == ffi::LUA_TNIL {
+                ffi::lua_remove(state, idx);
+                Ok(None)
+            } else {
+                let got_t = utils::type_of(state, idx);
+                ffi::lua_remove(state, idx);
+                let expected = if optional { "a vector or nil" } else { "a vector" };
+                push_wrapped_error(state, &format!("{}: expected '{}' to be {}, got {}", function_name, arg_name, expected, got_t));
+                Err(1)
+            }
         }
     }
     pub unsafe extern "C-unwind" fn replace_html(state: *mut ffi::lua_State) -> c_int {
@@ -85,21 +427,11 @@ impl WebviewIpc {
             }
         };
 
-        let new_html = unsafe {
-            if ffi::lua_type(state, -1) == ffi::LUA_TSTRING {
-                let ptr = ffi::lua_tostring(state, -1);
-                let s = BString::clone_from_ptr(ptr).to_str_lossy().to_string();
-                ffi::lua_pop(state, 1);
-                s
-            } else if ffi::lua_isnone(state, -1) == 1 {
-                push_wrapped_error(state, &format!("{}: called without required argument new_html", function_name));
-                return 1;
-            } else {
-                let got_t = utils::type_of(state, -1);
-                // pop whatever we got to balance stack
-                ffi::lua_pop(state, 1);
-                push_wrapped_error(state, &format!("{}: expected 'new_html' to be a string, got {}", function_name, got_t));
-                return 1;
+        // SAFETY: new_html is the only argument left on the stack after popping self
+        let new_html = match unsafe { Self::get_string(state, -1, function_name, "new_html") } {
+            Ok(s) => s,
+            Err(rets) => {
+                return rets;
             }
         };
 
@@ -110,6 +442,310 @@ impl WebviewIpc {
 
         0
     }
+    pub unsafe extern "C-unwind" fn navigate(state: *mut ffi::lua_State) -> c_int {
+        // index -2: WebviewIpc userdata, index -1: url to load
+
+        let function_name = "WebviewIpc:navigate(url: string)";
+
+        let top = unsafe { ffi::lua_gettop(state) };
+        if top != 2 {
+            push_wrapped_error(state, &format!("{}: called without required arguments; expected 2 arguments (self, string), got {}", function_name, top));
+            return 1;
+        }
+
+        let ipc = match unsafe { Self::get(state, -2, function_name) } {
+            Ok(ipc) => ipc,
+            Err(rets) => {
+                return rets;
+            }
+        };
+
+        let url = match unsafe { Self::get_string(state, -1, function_name, "url") } {
+            Ok(s) => s,
+            Err(rets) => {
+                return rets;
+            }
+        };
+
+        if let Err(err) = ipc.sender.send(ToWindow::Navigate(url)) {
+            push_wrapped_error(state, &format!("{}: unable to send message due to err: {}", function_name, err));
+            return 1;
+        }
+
+        0
+    }
+    pub unsafe extern "C-unwind" fn register_asset(state: *mut ffi::lua_State) -> c_int {
+        // index -3: WebviewIpc userdata, index -2: path, index -1: contents (with an optional mime after it)
+
+        let function_name = "WebviewIpc:register_asset(path: string, contents: string | buffer, mime: string?)";
+
+        let top = unsafe { ffi::lua_gettop(state) };
+        if top != 3 && top != 4 {
+            push_wrapped_error(state, &format!("{}: expected 3 or 4 arguments (self, string, string | buffer, string?), got {}", function_name, top));
+            return 1;
+        }
+
+        let mime = if top == 4 && unsafe { ffi::lua_type(state, -1) } != ffi::LUA_TNIL {
+            match unsafe { Self::get_string(state, -1, function_name, "mime") } {
+                Ok(mime) => Some(mime),
+                Err(rets) => {
+                    return rets;
+                }
+            }
+        } else {
+            if top == 4 {
+                // pop nil mime so contents is at -1
+                unsafe { ffi::lua_pop(state, 1) };
+            }
+            None
+        };
+
+        let ipc = match unsafe { Self::get(state, -3, function_name) } {
+            Ok(ipc) => ipc,
+            Err(rets) => {
+                return rets;
+            }
+        };
+
+        let path = match unsafe { Self::get_string(state, -2, function_name, "path") } {
+            Ok(s) => s,
+            Err(rets) => {
+                return rets;
+            }
+        };
+
+        let Some(contents) = (unsafe { utils::bytes_at(state, -1) }) else {
+            let got_t = unsafe { utils::type_of(state, -1) };
+            unsafe { ffi::lua_pop(state, 1) };
+            push_wrapped_error(state, &format!("{}: expected 'contents' to be a string or buffer, got {}", function_name, got_t));
+            return 1;
+        };
+        unsafe { ffi::lua_pop(state, 1) };
+
+        let asset = match Asset::new(&path, contents, mime.as_deref()) {
+            Ok(asset) => asset,
+            Err(err) => {
+                push_wrapped_error(state, &format!("{}: {}", function_name, err));
+                return 1;
+            }
+        };
+
+        if let Err(err) = ipc.sender.send(ToWindow::RegisterAsset(path, asset)) {
+            push_wrapped_error(state, &format!("{}: unable to send message due to err: {}", function_name, err));
+            return 1;
+        }
+
+        0
+    }
+    pub unsafe extern "C-unwind" fn add_init_script(state: *mut ffi::lua_State) -> c_int {
+        // index -2: WebviewIpc userdata, index -1: js
+
+        let function_name = "WebviewIpc:add_init_script(js: string)";
+
+        let top = unsafe { ffi::lua_gettop(state) };
+        if top != 2 {
+            push_wrapped_error(state, &format!("{}: called without required arguments; expected 2 arguments (self, string), got {}", function_name, top));
+            return 1;
+        }
+
+        let ipc = match unsafe { Self::get(state, -2, function_name) } {
+            Ok(ipc) => ipc,
+            Err(rets) => {
+                return rets;
+            }
+        };
+
+        let js = match unsafe { Self::get_string(state, -1, function_name, "js") } {
+            Ok(s) => s,
+            Err(rets) => {
+                return rets;
+            }
+        };
+
+        if let Err(err) = ipc.sender.send(ToWindow::AddInitScript(js)) {
+            push_wrapped_error(state, &format!("{}: unable to send message due to err: {}", function_name, err));
+            return 1;
+        }
+
+        0
+    }
+    pub unsafe extern "C-unwind" fn eval(state: *mut ffi::lua_State) -> c_int {
+        // index -2: WebviewIpc userdata, index -1: javascript code to evaluate
+
+        let function_name = "WebviewIpc:eval(code: string)";
+
+        let top = unsafe { ffi::lua_gettop(state) };
+        if top != 2 {
+            push_wrapped_error(state, &format!("{}: called without required arguments; expected 2 arguments (self, string), got {}", function_name, top));
+            return 1;
+        }
+
+        // SAFETY: idx -2 is the correct idx; 2 elements are expected to be passed to this function
+        let ipc = match unsafe { Self::get(state, -2, function_name) } {
+            Ok(ipc) => ipc,
+            Err(rets) => {
+                return rets;
+            }
+        };
+
+        let code = match unsafe { Self::get_string(state, -1, function_name, "code") } {
+            Ok(s) => s,
+            Err(rets) => {
+                return rets;
+            }
+        };
+
+        if let Err(err) = ipc.sender.send(ToWindow::EvaluateScript(code)) {
+            push_wrapped_error(state, &format!("{}: unable to send message due to err: {}", function_name, err));
+            return 1;
+        }
+
+        0
+    }
+    pub unsafe extern "C-unwind" fn eval_result(state: *mut ffi::lua_State) -> c_int {
+        // index 1: WebviewIpc userdata, index 2: javascript code to evaluate, index 3: optional timeout in seconds
+
+        let function_name = "WebviewIpc:eval_result(code: string, timeout: number?)";
+
+        let top = unsafe { ffi::lua_gettop(state) };
+        if top != 2 && top != 3 {
+            push_wrapped_error(state, &format!("{}: called without required arguments; expected 2 or 3 arguments (self, string, number?), got {}", function_name, top));
+            return 1;
+        }
+
+        let timeout = if top == 3 {
+            // pop timeout so self and code are at -2 and -1
+            match unsafe { Self::get_timeout(state, -1, function_name) } {
+                Ok(timeout) => timeout,
+                Err(rets) => {
+                    return rets;
+                }
+            }
+        } else {
+            None
+        };
+
+        // SAFETY: idx -2 is the correct idx; self and code are the only elements left on the stack
+        let ipc = match unsafe { Self::get(state, -2, function_name) } {
+            Ok(ipc) => ipc,
+            Err(rets) => {
+                return rets;
+            }
+        };
+
+        let code = match unsafe { Self::get_string(state, -1, function_name, "code") } {
+            Ok(s) => s,
+            Err(rets) => {
+                return rets;
+            }
+        };
+
+        let result = match ipc.request(|reply| ToWindow::EvaluateScriptWithResult(code, reply), timeout) {
+            Ok(result) => result,
+            Err(err) => {
+                push_wrapped_error(state, &format!("{}: {}", function_name, err));
+                return 1;
+            }
+        };
+
+        // the webview gives us an empty string when the script returns undefined or throws
+        if result.is_empty() {
+            unsafe { ffi::lua_pushnil(state) };
+        } else {
+            match serde_json::from_str(&result) {
+                Ok(value) => unsafe { json::push_value(state, &value) },
+                Err(_) => unsafe { ffi::lua_pushlstring(state, result.as_ptr() as *const _, result.len()) },
+            }
+        }
+
+        1
+    }
+    pub unsafe extern "C-unwind" fn send(state: *mut ffi::lua_State) -> c_int {
+        // index -2: WebviewIpc userdata, index -1: payload (string or table) to send to the page
+
+        let function_name = "WebviewIpc:send(payload: string | table)";
+
+        let top = unsafe { ffi::lua_gettop(state) };
+        if top != 2 {
+            push_wrapped_error(state, &format!("{}: called without required arguments; expected 2 arguments (self, payload), got {}", function_name, top));
+            return 1;
+        }
+
+        // SAFETY: idx -2 is the correct idx; 2 elements are expected to be passed to this function
+        let ipc = match unsafe { Self::get(state, -2, function_name) } {
+            Ok(ipc) => ipc,
+            Err(rets) => {
+                return rets;
+            }
+        };
+
+        // SAFETY: payload is the only element left on the stack
+        let payload = match unsafe { json::value_from_stack(state, -1) } {
+            Ok(payload) => payload,
+            Err(err) => {
+                unsafe { ffi::lua_pop(state, 1) };
+                push_wrapped_error(state, &format!("{}: unable to serialize payload: {}", function_name, err));
+                return 1;
+            }
+        };
+        unsafe { ffi::lua_pop(state, 1) };
+
+        if let Err(err) = ipc.sender.send(ToWindow::SendMessage(payload.to_string())) {
+            push_wrapped_error(state, &format!("{}: unable to send message due to err: {}", function_name, err));
+            return 1;
+        }
+
+        0
+    }
+    pub unsafe extern "C-unwind" fn bind(state: *mut ffi::lua_State) -> c_int {
+        // index -3: WebviewIpc userdata, index -2: name, index -1: function (or nil to unbind)
+
+        let function_name = "WebviewIpc:bind(name: string, f: ((...any) -> any)?)";
+
+        let top = unsafe { ffi::lua_gettop(state) };
+        if top != 3 {
+            push_wrapped_error(state, &format!("{}: called without required arguments; expected 3 arguments (self, string, function), got {}", function_name, top));
+            return 1;
+        }
+
+        // SAFETY: idx -3 is the correct idx; 3 elements are expected to be passed to this function
+        let ipc = match unsafe { Self::get(state, -3, function_name) } {
+            Ok(ipc) => ipc,
+            Err(rets) => {
+                return rets;
+            }
+        };
+
+        let name = match unsafe { Self::get_string(state, -2, function_name, "name") } {
+            Ok(s) => s,
+            Err(rets) => {
+                return rets;
+            }
+        };
+
+        let f_type = unsafe { ffi::lua_type(state, -1) };
+        let new_ref = if f_type == ffi::LUA_TFUNCTION {
+            Some(unsafe { ffi::lua_ref(state, -1) })
+        } else if f_type == ffi::LUA_TNIL {
+            None
+        } else {
+            let got_t = unsafe { utils::type_of(state, -1) };
+            unsafe { ffi::lua_pop(state, 1) };
+            push_wrapped_error(state, &format!("{}: expected 'f' to be a function or nil, got {}", function_name, got_t));
+            return 1;
+        };
+        unsafe { ffi::lua_pop(state, 1) };
+
+        let old_ref = match new_ref {
+            Some(new_ref) => ipc.bindings.borrow_mut().insert(name, new_ref),
+            None => ipc.bindings.borrow_mut().remove(&name),
+        };
+        if let Some(old_ref) = old_ref {
+            unsafe { ffi::lua_unref(state, old_ref) };
+        }
+
+        0
+    }
     pub unsafe extern "C-unwind" fn try_read(state: *mut ffi::lua_State) -> c_int {
         // index -1: WebviewIpc userdata
 
@@ -121,6 +757,43 @@ impl WebviewIpc {
             return 1;
         }
 
+        // keep a copy of self on the stack so bound functions can't let it get garbage collected mid-read
+        unsafe { ffi::lua_pushvalue(state, -1) };
+        let ipc = match unsafe { Self::get(state, -1, function_name) } {
+            Ok(ipc) => ipc,
+            Err(rets) => {
+                return rets;
+            }
+        };
+
+        // SAFETY: the copy of self was popped, only self is left on the stack
+        unsafe { Self::push_next_message(state, ipc, Wait::No) }
+    }
+    pub unsafe extern "C-unwind" fn read(state: *mut ffi::lua_State) -> c_int {
+        // index 1: WebviewIpc userdata, index 2: optional timeout in seconds
+
+        let function_name = "WebviewIpc:read(timeout: number?)";
+
+        let top = unsafe { ffi::lua_gettop(state) };
+        if top != 1 && top != 2 {
+            push_wrapped_error(state, &format!("{}: expected 1 or 2 arguments (self, number?), got {}", function_name, top));
+            return 1;
+        }
+
+        let timeout = if top == 2 {
+            // pop timeout so self is at -1
+            match unsafe { Self::get_timeout(state, -1, function_name) } {
+                Ok(timeout) => timeout,
+                Err(rets) => {
+                    return rets;
+                }
+            }
+        } else {
+            None
+        };
+
+        // keep a copy of self on the stack so bound functions can't let it get garbage collected mid-read
+        unsafe { ffi::lua_pushvalue(state, -1) };
         let ipc = match unsafe { Self::get(state, -1, function_name) } {
             Ok(ipc) => ipc,
             Err(rets) => {
@@ -128,30 +801,97 @@ impl WebviewIpc {
             }
         };
 
-        match ipc.receiver.try_recv() {
-            Ok(ToLuau::IpcMessage(message)) => {
-                let message = match CString::new(message) {
-                    Ok(s) => s,
-                    Err(err) => {
-                        let pos = err.nul_position();
-                        CString::new(format!("{}: IPC message contains NUL byte at {}", function_name, pos)).unwrap()
-                    }
-                };
+        // a deadline too far off to represent is as good as none, which is what recv_timeout does too
+        let wait = match timeout.and_then(|timeout| Instant::now().checked_add(timeout)) {
+            Some(deadline) => Wait::Until(deadline),
+            None => Wait::Forever,
+        };
+
+        // SAFETY: the copy of self was popped, only self is left on the stack
+        unsafe { Self::push_next_message(state, ipc, wait) }
+    }
+    /// Pushes the next event for `try_read`/`read` onto the Luau stack as a table tagged with `kind`,
+    /// answering `seal.invoke` calls and protocol requests along the way. Pushes nil if `wait` runs out first.
+    ///
+    /// # Safety
+    /// - state must be a non-null pointer to a lua_State
+    unsafe fn push_next_message(state: *mut ffi::lua_State, ipc: &Self, wait: Wait) -> c_int {
+        let message = loop {
+            match ipc.next_message(wait) {
+                Ok(ToLuau::Invoke(id, name, args)) => {
+                    unsafe { ipc.answer_invoke(state, id, &name, args) };
+                },
+                Ok(ToLuau::ProtocolRequest(id, scheme, request)) => {
+                    unsafe { ipc.answer_protocol(state, id, &scheme, request) };
+                },
+                message => break message,
+            }
+        };
 
-                unsafe { ffi::lua_pushstring(state, message.as_ptr()) };
+        unsafe { ffi::luaL_checkstack(state, 3, c"can't push event".as_ptr()) };
+        match message {
+            Ok(ToLuau::IpcMessage(message)) => unsafe {
+                push_event(state, c"message");
+                ffi::lua_pushlstring(state, message.as_ptr() as *const _, message.len());
+                ffi::lua_setfield(state, -2, c"data".as_ptr());
+            },
+            Ok(ToLuau::JsonMessage(value)) => unsafe {
+                push_event(state, c"message");
+                json::push_value(state, &value);
+                ffi::lua_setfield(state, -2, c"data".as_ptr());
+            },
+            // the UI thread drops its sender when the window closes, so a disconnected channel means closed too
+            Ok(ToLuau::WindowClosed) | Err(RecvTimeoutError::Disconnected) => unsafe {
+                push_event(state, c"closed");
+            },
+            Ok(ToLuau::Resized(width, height)) => unsafe {
+                push_event(state, c"resized");
+                ffi::lua_pushvector(state, width, height, 0.0);
+                ffi::lua_setfield(state, -2, c"size".as_ptr());
+            },
+            Ok(ToLuau::Moved(x, y)) => unsafe {
+                push_event(state, c"moved");
+                ffi::lua_pushvector(state, x, y, 0.0);
+                ffi::lua_setfield(state, -2, c"position".as_ptr());
+            },
+            Ok(ToLuau::Focused(focused)) => unsafe {
+                push_event(state, c"focused");
+                ffi::lua_pushboolean(state, focused as c_int);
+                ffi::lua_setfield(state, -2, c"focused".as_ptr());
+            },
+            Ok(ToLuau::ScaleFactorChanged(scale_factor, width, height)) => unsafe {
+                push_event(state, c"scale_factor_changed");
+                ffi::lua_pushnumber(state, scale_factor);
+                ffi::lua_setfield(state, -2, c"scale_factor".as_ptr());
+                ffi::lua_pushvector(state, width, height, 0.0);
+                ffi::lua_setfield(state, -2, c"size".as_ptr());
             },
-            Ok(ToLuau::WindowClosed) => {
-                push_wrapped_error(state, "the window has been closed");
+            Ok(ToLuau::ThemeChanged(theme)) => unsafe {
+                push_event(state, c"theme_changed");
+                ffi::lua_pushlstring(state, theme.as_ptr() as *const _, theme.len());
+                ffi::lua_setfield(state, -2, c"theme".as_ptr());
             },
-            Ok(ToLuau::SizeReturned(_, _)) => unreachable!("only reachable from WindowIpc:size()"),
-            Err(TryRecvError::Disconnected) => {
-                push_wrapped_error(state, "channel is disconnected");
+            Ok(ToLuau::FileDropped(path)) => unsafe {
+                push_event(state, c"file_dropped");
+                let path = path.to_string_lossy();
+                ffi::lua_pushlstring(state, path.as_ptr() as *const _, path.len());
+                ffi::lua_setfield(state, -2, c"path".as_ptr());
             },
-            Err(TryRecvError::Empty) => {
-                unsafe { ffi::lua_pushnil(state) };
+            Ok(ToLuau::FileHovered(path)) => unsafe {
+                push_event(state, c"file_hovered");
+                let path = path.to_string_lossy();
+                ffi::lua_pushlstring(state, path.as_ptr() as *const _, path.len());
+                ffi::lua_setfield(state, -2, c"path".as_ptr());
+            },
+            Ok(ToLuau::FileHoverCancelled) => unsafe {
+                push_event(state, c"file_hover_cancelled");
+            },
+            Ok(ToLuau::Invoke(_, _, _)) | Ok(ToLuau::ProtocolRequest(_, _, _)) => unreachable!("handled above"),
+            Err(RecvTimeoutError::Timeout) => unsafe {
+                ffi::lua_pushnil(state);
             }
         }
-        
+
         1
     }
      pub unsafe extern "C-unwind" fn alert(state: *mut ffi::lua_State) -> c_int {
@@ -209,27 +949,239 @@ impl WebviewIpc {
             }
         };
 
-        if let Err(err) = ipc.sender.send(ToWindow::SizeRequested) {
-            push_wrapped_error(state, &format!("{}: unable to send request for size due to err {}", function_name, err));
-            return 1;
-        };
-
-        match ipc.receiver.recv() {
-            Ok(ToLuau::SizeReturned(width, height)) => {
+        match ipc.request(ToWindow::SizeRequested, Some(QUERY_TIMEOUT)) {
+            Ok((width, height)) => {
                 unsafe { ffi::lua_pushvector(state, width, height, 0.0) };
             },
-            Ok(t) => {
-                push_wrapped_error(state, &format!("{}: unexpected message type returned: {:?}", function_name, t));
-                return 1;
-            }
             Err(err) => {
-                push_wrapped_error(state, &format!("{}: unable to recv due to err: {}", function_name, err));
+                push_wrapped_error(state, &format!("{}: {}", function_name, err));
                 return 1;
             }
         };
 
         1
     }
+    /// Asks the UI thread to answer `query` and pushes the reply onto the Luau stack.
+    ///
+    /// # Safety
+    /// - state must be a non-null pointer to a lua_State, with self as the only argument
+    unsafe fn query(state: *mut ffi::lua_State, function_name: &'static str, query: WindowQuery) -> c_int {
+        // self should be at idx -1
+        let top = unsafe { ffi::lua_gettop(state) };
+        if top != 1 {
+            push_wrapped_error(state, &format!("{}: expected to be called with only self, got {} arguments", function_name, top));
+            return 1;
+        }
+
+        // SAFETY: idx -1 is the correct idx for only self fn
+        let ipc = match unsafe { Self::get(state, -1, function_name) } {
+            Ok(ipc) => ipc,
+            Err(rets) => {
+                return rets;
+            }
+        };
+
+        match ipc.request(|reply| ToWindow::Query(query, reply), Some(QUERY_TIMEOUT)) {
+            Ok(reply) => unsafe { reply.push(state) },
+            Err(err) => {
+                push_wrapped_error(state, &format!("{}: {}", function_name, err));
+            }
+        }
+
+        1
+    }
+    pub unsafe extern "C-unwind" fn position(state: *mut ffi::lua_State) -> c_int {
+        unsafe { Self::query(state, "WebviewIpc:position()", WindowQuery::Position) }
+    }
+    pub unsafe extern "C-unwind" fn outer_size(state: *mut ffi::lua_State) -> c_int {
+        unsafe { Self::query(state, "WebviewIpc:outer_size()", WindowQuery::OuterSize) }
+    }
+    pub unsafe extern "C-unwind" fn is_maximized(state: *mut ffi::lua_State) -> c_int {
+        unsafe { Self::query(state, "WebviewIpc:is_maximized()", WindowQuery::IsMaximized) }
+    }
+    pub unsafe extern "C-unwind" fn is_minimized(state: *mut ffi::lua_State) -> c_int {
+        unsafe { Self::query(state, "WebviewIpc:is_minimized()", WindowQuery::IsMinimized) }
+    }
+    pub unsafe extern "C-unwind" fn is_focused(state: *mut ffi::lua_State) -> c_int {
+        unsafe { Self::query(state, "WebviewIpc:is_focused()", WindowQuery::IsFocused) }
+    }
+    pub unsafe extern "C-unwind" fn is_visible(state: *mut ffi::lua_State) -> c_int {
+        unsafe { Self::query(state, "WebviewIpc:is_visible()", WindowQuery::IsVisible) }
+    }
+    pub unsafe extern "C-unwind" fn is_fullscreen(state: *mut ffi::lua_State) -> c_int {
+        unsafe { Self::query(state, "WebviewIpc:is_fullscreen()", WindowQuery::IsFullscreen) }
+    }
+    pub unsafe extern "C-unwind" fn scale_factor(state: *mut ffi::lua_State) -> c_int {
+        unsafe { Self::query(state, "WebviewIpc:scale_factor()", WindowQuery::ScaleFactor) }
+    }
+    pub unsafe extern "C-unwind" fn title(state: *mut ffi::lua_State) -> c_int {
+        unsafe { Self::query(state, "WebviewIpc:title()", WindowQuery::Title) }
+    }
+    pub unsafe extern "C-unwind" fn current_monitor(state: *mut ffi::lua_State) -> c_int {
+        unsafe { Self::query(state, "WebviewIpc:current_monitor()", WindowQuery::CurrentMonitor) }
+    }
+    /// Sends `command` to the UI thread; for setters that take only self.
+    ///
+    /// # Safety
+    /// - state must be a non-null pointer to a lua_State
+    unsafe fn command(state: *mut ffi::lua_State, function_name: &'static str, command: WindowCommand) -> c_int {
+        // self should be at idx -1
+        let top = unsafe { ffi::lua_gettop(state) };
+        if top != 1 {
+            push_wrapped_error(state, &format!("{}: expected to be called with only self, got {} arguments", function_name, top));
+            return 1;
+        }
+
+        let ipc = match unsafe { Self::get(state, -1, function_name) } {
+            Ok(ipc) => ipc,
+            Err(rets) => {
+                return rets;
+            }
+        };
+
+        unsafe { Self::send_command(state, ipc, function_name, command) }
+    }
+    /// Sends the command built from a boolean argument to the UI thread; for setters like `set_visible(visible)`.
+    ///
+    /// # Safety
+    /// - state must be a non-null pointer to a lua_State
+    unsafe fn bool_command(state: *mut ffi::lua_State, function_name: &'static str, arg_name: &'static str, make_command: fn(bool) -> WindowCommand) -> c_int {
+        // self at idx -2, boolean at idx -1
+        let top = unsafe { ffi::lua_gettop(state) };
+        if top != 2 {
+            push_wrapped_error(state, &format!("{}: expected to be called with 2 arguments (self, boolean), got {}", function_name, top));
+            return 1;
+        }
+
+        let ipc = match unsafe { Self::get(state, -2, function_name) } {
+            Ok(ipc) => ipc,
+            Err(rets) => {
+                return rets;
+            }
+        };
+
+        let b = match unsafe { Self::get_bool(state, -1, function_name, arg_name) } {
+            Ok(b) => b,
+            Err(rets) => {
+                return rets;
+            }
+        };
+
+        unsafe { Self::send_command(state, ipc, function_name, make_command(b)) }
+    }
+    /// Sends the command built from a vector argument to the UI thread; for setters like `set_size(size)`.
+    /// The vector may be nil (None) if `optional`.
+    ///
+    /// # Safety
+    /// - state must be a non-null pointer to a lua_State
+    unsafe fn vector_command(state: *mut ffi::lua_State, function_name: &'static str, arg_name: &'static str, optional: bool, make_command: fn(Option<(f32, f32)>) -> WindowCommand) -> c_int {
+        // self at idx -2, vector at idx -1
+        let top = unsafe { ffi::lua_gettop(state) };
+        if top != 2 {
+            push_wrapped_error(state, &format!("{}: expected to be called with 2 arguments (self, vector), got {}", function_name, top));
+            return 1;
+        }
+
+        let ipc = match unsafe { Self::get(state, -2, function_name) } {
+            Ok(ipc) => ipc,
+            Err(rets) => {
+                return rets;
+            }
+        };
+
+        let xy = match unsafe { Self::get_vector(state, -1, function_name, arg_name, optional) } {
+            Ok(xy) => xy,
+            Err(rets) => {
+                return rets;
+            }
+        };
+
+        unsafe { Self::send_command(state, ipc, function_name, make_command(xy)) }
+    }
+    /// # Safety
+    /// - state must be a non-null pointer to a lua_State
+    unsafe fn send_command(state: *mut ffi::lua_State, ipc: &Self, function_name: &'static str, command: WindowCommand) -> c_int {
+        if let Err(err) = ipc.sender.send(ToWindow::Command(command)) {
+            push_wrapped_error(state, &format!("{}: unable to send message to window due to err: {}", function_name, err));
+            return 1;
+        }
+        0
+    }
+    pub unsafe extern "C-unwind" fn set_title(state: *mut ffi::lua_State) -> c_int {
+        // self at idx -2, title at idx -1
+        let function_name = "WebviewIpc:set_title(title: string)";
+
+        let top = unsafe { ffi::lua_gettop(state) };
+        if top != 2 {
+            push_wrapped_error(state, &format!("{}: expected to be called with 2 arguments (self, string), got {}", function_name, top));
+            return 1;
+        }
+
+        let ipc = match unsafe { Self::get(state, -2, function_name) } {
+            Ok(ipc) => ipc,
+            Err(rets) => {
+                return rets;
+            }
+        };
+
+        let title = match unsafe { Self::get_string(state, -1, function_name, "title") } {
+            Ok(s) => s,
+            Err(rets) => {
+                return rets;
+            }
+        };
+
+        unsafe { Self::send_command(state, ipc, function_name, WindowCommand::SetTitle(title)) }
+    }
+    pub unsafe extern "C-unwind" fn set_size(state: *mut ffi::lua_State) -> c_int {
+        unsafe {
+            Self::vector_command(state, "WebviewIpc:set_size(size: vector)", "size", false, |size| {
+                let (width, height) = size.expect("size isn't optional");
+                WindowCommand::SetSize(width, height)
+            })
+        }
+    }
+    pub unsafe extern "C-unwind" fn set_position(state: *mut ffi::lua_State) -> c_int {
+        unsafe {
+            Self::vector_command(state, "WebviewIpc:set_position(position: vector)", "position", false, |position| {
+                let (x, y) = position.expect("position isn't optional");
+                WindowCommand::SetPosition(x, y)
+            })
+        }
+    }
+    pub unsafe extern "C-unwind" fn center(state: *mut ffi::lua_State) -> c_int {
+        unsafe { Self::command(state, "WebviewIpc:center()", WindowCommand::Center) }
+    }
+    pub unsafe extern "C-unwind" fn set_min_size(state: *mut ffi::lua_State) -> c_int {
+        unsafe { Self::vector_command(state, "WebviewIpc:set_min_size(size: vector?)", "size", true, WindowCommand::SetMinSize) }
+    }
+    pub unsafe extern "C-unwind" fn set_max_size(state: *mut ffi::lua_State) -> c_int {
+        unsafe { Self::vector_command(state, "WebviewIpc:set_max_size(size: vector?)", "size", true, WindowCommand::SetMaxSize) }
+    }
+    pub unsafe extern "C-unwind" fn set_resizable(state: *mut ffi::lua_State) -> c_int {
+        unsafe { Self::bool_command(state, "WebviewIpc:set_resizable(resizable: boolean)", "resizable", WindowCommand::SetResizable) }
+    }
+    pub unsafe extern "C-unwind" fn set_always_on_top(state: *mut ffi::lua_State) -> c_int {
+        unsafe { Self::bool_command(state, "WebviewIpc:set_always_on_top(always_on_top: boolean)", "always_on_top", WindowCommand::SetAlwaysOnTop) }
+    }
+    pub unsafe extern "C-unwind" fn set_fullscreen(state: *mut ffi::lua_State) -> c_int {
+        unsafe { Self::bool_command(state, "WebviewIpc:set_fullscreen(fullscreen: boolean)", "fullscreen", WindowCommand::SetFullscreen) }
+    }
+    pub unsafe extern "C-unwind" fn set_visible(state: *mut ffi::lua_State) -> c_int {
+        unsafe { Self::bool_command(state, "WebviewIpc:set_visible(visible: boolean)", "visible", WindowCommand::SetVisible) }
+    }
+    pub unsafe extern "C-unwind" fn focus(state: *mut ffi::lua_State) -> c_int {
+        unsafe { Self::command(state, "WebviewIpc:focus()", WindowCommand::Focus) }
+    }
+    pub unsafe extern "C-unwind" fn minimize(state: *mut ffi::lua_State) -> c_int {
+        unsafe { Self::command(state, "WebviewIpc:minimize()", WindowCommand::Minimize) }
+    }
+    pub unsafe extern "C-unwind" fn maximize(state: *mut ffi::lua_State) -> c_int {
+        unsafe { Self::command(state, "WebviewIpc:maximize()", WindowCommand::Maximize) }
+    }
+    pub unsafe extern "C-unwind" fn unmaximize(state: *mut ffi::lua_State) -> c_int {
+        unsafe { Self::command(state, "WebviewIpc:unmaximize()", WindowCommand::Unmaximize) }
+    }
     pub unsafe extern "C-unwind" fn close(state: *mut ffi::lua_State) -> c_int {
         // WebviewIpc should be at stack index -1
 
@@ -255,5 +1207,47 @@ impl WebviewIpc {
 
         0
     }
+    pub unsafe extern "C-unwind" fn destroy(state: *mut ffi::lua_State) -> c_int {
+        // WebviewIpc should be at stack index -1
+
+        let function_name = "WebviewIpc:destroy()";
+
+        let top = unsafe { ffi::lua_gettop(state) };
+        if top != 1 {
+            push_wrapped_error(state, &format!("{}: expected to be called with only self, got {} arguments", function_name, top));
+            return 1;
+        }
+
+        let ud_ptr = match unsafe { Self::get_userdata(state, -1, function_name) } {
+            Ok(ud_ptr) => ud_ptr,
+            Err(rets) => {
+                return rets;
+            }
+        };
+
+        // SAFETY: ud_ptr points to userdata tagged WEBVIEW_IPC_TAG, which holds a *mut WebviewIpc
+        // from Box::into_raw, or null once destroyed
+        unsafe {
+            let ipc_ptr = *ud_ptr;
+            if ipc_ptr.is_null() {
+                // already destroyed
+                return 0;
+            }
+            if (*ipc_ptr).binding_depth.get() > 0 {
+                push_wrapped_error(state, &format!("{}: can't destroy a window from inside one of its bound functions, use close() instead", function_name));
+                return 1;
+            }
+
+            *ud_ptr = std::ptr::null_mut();
+            let ipc = Box::from_raw(ipc_ptr);
+            for function_ref in ipc.bindings.take().into_values().chain(ipc.protocols.values().copied()) {
+                ffi::lua_unref(state, function_ref);
+            }
+            // dropping the WebviewIpc closes the window
+            drop(ipc);
+        }
+
+        0
+    }
 
 }
\ No newline at end of file
@@ -0,0 +1,548 @@
+use std::rc::Rc;
+
+use crossbeam_channel::{Receiver, Sender, TryRecvError};
+use tao::{
+    dpi::{LogicalSize, PhysicalPosition},
+    event::WindowEvent,
+    event_loop::{EventLoopProxy, EventLoopWindowTarget},
+    window::{Fullscreen, Theme, UserAttentionType, Window, WindowBuilder, WindowId},
+};
+use wry::{PageLoadEvent, WebView, WebViewBuilder, http::{Request, StatusCode}};
+
+use crate::command::WindowCommand;
+use crate::control;
+use crate::luau_protocol::{self, PendingRequests};
+use crate::options::{Content, Decorations, EventMask, IpcMode, WebviewOptions};
+use crate::protocol::{self, SealProtocol};
+use crate::resize::{self, HitTestResult};
+use crate::ui::UiRequest;
+use crate::{ToLuau, ToWindow};
+
+const HTML_HEADER: &str = include_str!("./template.html");
+/// Page without webseal's titlebar, for windows that don't use `Decorations::Custom`.
+const BARE_HTML: &str = include_str!("./bare.html");
+/// Defines `window.seal`; injected as an initialization script so it's in every page,
+/// including ones loaded from a url or file. `!REPLACECONTROLURL!` is where it sends control messages.
+const BRIDGE_JS: &str = include_str!("./bridge.js");
+
+/// Builds the page around `body`, wrapped in webseal's titlebar if the window uses it.
+fn page_html(decorations: Decorations, title: &str, body: &str) -> String {
+    let template = match decorations {
+        Decorations::Custom => HTML_HEADER,
+        Decorations::Native | Decorations::None => BARE_HTML,
+    };
+    template
+        .replace("!REPLACETITLE!", title)
+        .replace("!REPLACEBODY!", body)
+}
+
+/// Runs `js` at the start of every page the webview loads from now on, ahead of the page's own scripts.
+///
+/// wry only takes initialization scripts while building the webview, so this adds one the same way it does.
+#[cfg(not(any(
+    target_os = "windows",
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "android"
+)))]
+fn add_init_script(webview: &WebView, js: &str) -> Result<(), String> {
+    use webkit2gtk::{UserContentInjectedFrames, UserContentManagerExt, UserScript, UserScriptInjectionTime, WebViewExt};
+    use wry::WebViewExtUnix;
+    let Some(manager) = webview.webview().user_content_manager() else {
+        return Err(String::from("the webview has no user content manager"));
+    };
+    let script = UserScript::new(js, UserContentInjectedFrames::TopFrame, UserScriptInjectionTime::Start, &[], &[]);
+    manager.add_script(&script);
+    Ok(())
+}
+#[cfg(target_os = "windows")]
+fn add_init_script(webview: &WebView, js: &str) -> Result<(), String> {
+    use webview2_com::AddScriptToExecuteOnDocumentCreatedCompletedHandler;
+    use windows_core::HSTRING;
+    use wry::WebViewExtWindows;
+    // it answers with an id for removing the script again, which nothing does
+    let handler = AddScriptToExecuteOnDocumentCreatedCompletedHandler::create(Box::new(|_, _| Ok(())));
+    unsafe { webview.webview().AddScriptToExecuteOnDocumentCreated(&HSTRING::from(js), &handler) }
+        .map_err(|err| err.to_string())
+}
+/// Not wired up elsewhere yet; `init_scripts` still works there.
+#[cfg(any(target_os = "macos", target_os = "ios", target_os = "android"))]
+fn add_init_script(_webview: &WebView, _js: &str) -> Result<(), String> {
+    Err(String::from("adding init scripts after the window is built isn't supported on this platform"))
+}
+
+/// Input from the page's titlebar, IPC handler and page load handler, routed to its window by the UI thread.
+///
+/// Everything but `SendIpc` and `PageLoaded` comes from the control channel, see `control::parse_request`.
+#[derive(Debug)]
+pub enum UserEvent {
+    Minimize,
+    Maximize,
+    DragWindow,
+    CloseWindow,
+    MouseDown(i32, i32),
+    MouseMove(i32, i32),
+    SendIpc(String),
+    Invoke(u64, String, serde_json::Value),
+    /// The page finished loading.
+    PageLoaded,
+}
+
+/// A window and its webview, owned by the UI thread.
+pub struct WebviewWindow {
+    // fields drop in order, and the webview has to go before the window it's embedded in
+    webview: WebView,
+    window: Window,
+    options: WebviewOptions,
+    // set_title can change this, and replace_html needs the current one for the titlebar
+    title: String,
+    /// Serves the page, `root_dir` and assets over `seal://app/`.
+    protocol: Rc<SealProtocol>,
+    /// Requests to the `protocols` option waiting on Luau to answer them.
+    requests: Rc<PendingRequests>,
+    /// Whether the first page has loaded yet.
+    loaded: bool,
+    /// `eval_result` calls from before the first page loaded; webkitgtk queues scripts until then
+    /// but drops their callbacks, so they're held here instead.
+    pending_results: Vec<(String, Sender<String>)>,
+    sender: Sender<ToLuau>,
+    receiver: Receiver<ToWindow>,
+}
+
+/// Where a window of logical `size` has to go to be centered on the primary monitor,
+/// since tao can only center windows after they're built.
+fn centered_position(target: &EventLoopWindowTarget<UiRequest>, size: (f32, f32)) -> Option<PhysicalPosition<i32>> {
+    let monitor = target
+        .primary_monitor()
+        .or_else(|| target.available_monitors().next())?;
+    let scale_factor = monitor.scale_factor();
+    let monitor_position = monitor.position();
+    let monitor_size = monitor.size();
+    let width = (size.0 as f64 * scale_factor) as i32;
+    let height = (size.1 as f64 * scale_factor) as i32;
+    Some(PhysicalPosition::new(
+        monitor_position.x + (monitor_size.width as i32 - width) / 2,
+        monitor_position.y + (monitor_size.height as i32 - height) / 2,
+    ))
+}
+
+#[cfg(target_os = "windows")]
+fn with_skip_taskbar(builder: WindowBuilder, skip_taskbar: bool) -> WindowBuilder {
+    use tao::platform::windows::WindowBuilderExtWindows;
+    builder.with_skip_taskbar(skip_taskbar)
+}
+#[cfg(not(any(
+    target_os = "windows",
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "android"
+)))]
+fn with_skip_taskbar(builder: WindowBuilder, skip_taskbar: bool) -> WindowBuilder {
+    use tao::platform::unix::WindowBuilderExtUnix;
+    builder.with_skip_taskbar(skip_taskbar)
+}
+/// There's no taskbar to skip elsewhere.
+#[cfg(any(target_os = "macos", target_os = "ios", target_os = "android"))]
+fn with_skip_taskbar(builder: WindowBuilder, _skip_taskbar: bool) -> WindowBuilder {
+    builder
+}
+
+impl WebviewWindow {
+    pub fn build(
+        mut options: WebviewOptions,
+        sender: Sender<ToLuau>,
+        receiver: Receiver<ToWindow>,
+        target: &EventLoopWindowTarget<UiRequest>,
+        proxy: &EventLoopProxy<UiRequest>,
+    ) -> Result<Self, String> {
+        // only the custom titlebar needs a see-through window by default, for its rounded corners
+        let transparent = options.transparent.unwrap_or(options.decorations == Decorations::Custom);
+        // everything is set up front since changing it after the window shows up makes it flicker
+        let builder = WindowBuilder::new()
+            .with_decorations(options.decorations == Decorations::Native)
+            .with_transparent(transparent)
+            .with_title(&options.title)
+            .with_inner_size(LogicalSize::new(options.size.0, options.size.1))
+            .with_resizable(options.resizeable)
+            .with_maximized(options.maximized)
+            .with_fullscreen(options.fullscreen.then_some(Fullscreen::Borderless(None)))
+            .with_always_on_top(options.always_on_top)
+            .with_always_on_bottom(options.always_on_bottom)
+            .with_visible(options.visible)
+            .with_focused(options.focused)
+            .with_visible_on_all_workspaces(options.visible_on_all_workspaces);
+
+        // why do none of these methods take in &self instead of the whole self
+        let builder = if let Some(max_size) = options.max_size {
+            builder.with_max_inner_size(LogicalSize::new(max_size.0, max_size.1))
+        } else {
+            builder
+        };
+
+        let builder = if let Some(min_size) = options.min_size {
+            builder.with_min_inner_size(LogicalSize::new(min_size.0, min_size.1))
+        } else {
+            builder
+        };
+
+        let position = if options.center {
+            centered_position(target, options.size)
+        } else {
+            options.position.map(|(x, y)| PhysicalPosition::new(x as i32, y as i32))
+        };
+        let builder = if let Some(position) = position {
+            builder.with_position(position)
+        } else {
+            builder
+        };
+
+        let builder = with_skip_taskbar(builder, options.skip_taskbar);
+
+        let window = match builder.build(target) {
+            Ok(window) => window,
+            Err(err) => return Err(format!("unable to create window due to err: {}", err)),
+        };
+
+        let window_id = window.id();
+        let handler_proxy = proxy.clone();
+        let send = move |event: UserEvent| {
+            let _ = handler_proxy.send_event(UiRequest::Webview(window_id, event));
+        };
+        let load_proxy = proxy.clone();
+        let load_handler = move |event: PageLoadEvent, _url: String| {
+            if let PageLoadEvent::Finished = event {
+                let _ = load_proxy.send_event(UiRequest::Webview(window_id, UserEvent::PageLoaded));
+            }
+        };
+        let control_send = send.clone();
+        // control messages come in over seal://, so everything posted here belongs to Luau
+        let handler = move |req: Request<String>| send(UserEvent::SendIpc(req.into_body()));
+
+        let bridge = BRIDGE_JS.replace("!REPLACECONTROLURL!", &protocol::app_url(control::CONTROL_PATH));
+        let mut builder = WebViewBuilder::new()
+            .with_initialization_script(bridge)
+            .with_transparent(transparent)
+            .with_ipc_handler(handler)
+            .with_on_page_load_handler(load_handler)
+            .with_accept_first_mouse(true);
+        // after the bridge, so they can use window.seal
+        for script in &options.init_scripts {
+            builder = builder.with_initialization_script(script);
+        }
+
+        let protocol = Rc::new(SealProtocol::new(options.root_dir.clone()));
+        for (path, asset) in std::mem::take(&mut options.assets) {
+            protocol.register_asset(&path, asset);
+        }
+        let handler_protocol = Rc::clone(&protocol);
+        let builder = builder.with_custom_protocol(protocol::SCHEME.to_string(), move |_, request| {
+            control::handle_request(&request, &control_send).unwrap_or_else(|| handler_protocol.handle(&request))
+        });
+
+        let requests = Rc::new(PendingRequests::default());
+        let mut builder = builder;
+        for scheme in options.protocols.keys() {
+            let requests = Rc::clone(&requests);
+            let sender = sender.clone();
+            let name = scheme.clone();
+            builder = builder.with_asynchronous_custom_protocol(scheme.clone(), move |_, request, responder| {
+                // luau answers whenever it next reads, so the responder waits here until then
+                let id = requests.next_id();
+                match sender.send(ToLuau::ProtocolRequest(id, name.clone(), Box::new(request))) {
+                    Ok(()) => requests.insert(id, responder),
+                    Err(_) => responder.respond(luau_protocol::error_response(
+                        StatusCode::SERVICE_UNAVAILABLE,
+                        String::from("the window's handle is gone"),
+                    )),
+                }
+            });
+        }
+
+        let builder = match &options.content {
+            Content::Html(html) => {
+                // served from seal://app/ so relative links in it resolve against root_dir and assets
+                *protocol.page.borrow_mut() = Some(page_html(options.decorations, &options.title, html));
+                builder.with_url(protocol::app_url(""))
+            },
+            Content::Url(url) => builder.with_url(url),
+        };
+
+        #[cfg(any(
+            target_os = "windows",
+            target_os = "macos",
+            target_os = "ios",
+            target_os = "android"
+        ))]
+        let webview = builder.build(&window);
+        #[cfg(not(any(
+            target_os = "windows",
+            target_os = "macos",
+            target_os = "ios",
+            target_os = "android"
+        )))]
+        let webview = {
+            use tao::platform::unix::WindowExtUnix;
+            use wry::WebViewBuilderExtUnix;
+            match window.default_vbox() {
+                Some(vbox) => builder.build_gtk(vbox),
+                None => return Err(String::from("unable to create webview: the window has no gtk box to put it in")),
+            }
+        };
+        let webview = match webview {
+            Ok(webview) => webview,
+            Err(err) => return Err(format!("unable to create webview due to err: {}", err)),
+        };
+
+        let title = options.title.clone();
+        Ok(Self {
+            webview,
+            window,
+            options,
+            title,
+            protocol,
+            requests,
+            loaded: false,
+            pending_results: Vec::new(),
+            sender,
+            receiver,
+        })
+    }
+
+    pub fn id(&self) -> WindowId {
+        self.window.id()
+    }
+
+    /// Handles every command Luau has queued up.
+    ///
+    /// Returns false once the window should be closed.
+    pub fn handle_commands(&mut self) -> bool {
+        loop {
+            match self.receiver.try_recv() {
+                Ok(command) => {
+                    if !self.handle_command(command) {
+                        return false;
+                    }
+                },
+                Err(TryRecvError::Empty) => return true,
+                // nobody is left to talk to the window
+                Err(TryRecvError::Disconnected) => return false,
+            }
+        }
+    }
+
+    /// Returns false once the window should be closed.
+    fn handle_command(&mut self, command: ToWindow) -> bool {
+        let webview = &self.webview;
+        let window = &self.window;
+        match command {
+            ToWindow::ReplaceHtml(new_html) => {
+                let html = page_html(self.options.decorations, &self.title, &new_html);
+                *self.protocol.page.borrow_mut() = Some(html);
+                if let Err(err) = webview.load_url(&protocol::app_url("")) {
+                    eprintln!("unable to replace html due to err: {}", err);
+                }
+            },
+            ToWindow::RegisterAsset(path, asset) => {
+                self.protocol.register_asset(&path, asset);
+            },
+            ToWindow::Navigate(url) => {
+                if let Err(err) = webview.load_url(&url) {
+                    eprintln!("unable to navigate to {} due to err: {}", url, err);
+                }
+            },
+            ToWindow::EvaluateScript(js) => {
+                if let Err(err) = webview.evaluate_script(&js) {
+                    eprintln!("unable to evaluate script due to err: {}", err);
+                }
+            },
+            ToWindow::AddInitScript(js) => {
+                if let Err(err) = add_init_script(webview, &js) {
+                    eprintln!("unable to add init script due to err: {}", err);
+                }
+            },
+            ToWindow::EvaluateScriptWithResult(js, reply) => {
+                if self.loaded {
+                    self.evaluate_with_result(&js, reply);
+                } else {
+                    self.pending_results.push((js, reply));
+                }
+            },
+            ToWindow::SendMessage(payload) => {
+                // JSON is a valid JS expression, so the payload can be passed in directly
+                let js = format!("window.seal && window.seal.__receive({})", payload);
+                if let Err(err) = webview.evaluate_script(&js) {
+                    eprintln!("unable to send message to page due to err: {}", err);
+                }
+            },
+            ToWindow::InvokeResult(id, result) => {
+                let js = match result {
+                    Ok(value) => format!("window.seal && window.seal.__settle({}, true, {})", id, value),
+                    Err(message) => format!("window.seal && window.seal.__settle({}, false, {})", id, serde_json::Value::String(message)),
+                };
+                if let Err(err) = webview.evaluate_script(&js) {
+                    eprintln!("unable to settle seal.invoke call due to err: {}", err);
+                }
+            },
+            ToWindow::ProtocolResponse(id, response) => {
+                self.requests.respond(id, *response);
+            },
+            ToWindow::SetAlert(enabled) => {
+                if enabled {
+                    window.request_user_attention(Some(UserAttentionType::Critical));
+                } else {
+                    window.request_user_attention(None);
+                }
+            },
+            ToWindow::Close => {
+                self.report_closed();
+                return false;
+            },
+            ToWindow::Destroy => {
+                // nobody is left to tell
+                return false;
+            },
+            ToWindow::SizeRequested(reply) => {
+                let size = window.inner_size();
+                let _ = reply.send((size.width as f32, size.height as f32));
+            }
+            ToWindow::Query(query, reply) => {
+                let _ = reply.send(query.answer(window));
+            }
+            ToWindow::Command(command) => {
+                if let WindowCommand::SetTitle(new_title) = &command {
+                    // keep the custom titlebar in sync with the native title
+                    let js = format!(
+                        "(document.getElementById('seal-titlebar-title') || {{}}).textContent = {}",
+                        serde_json::Value::String(new_title.clone())
+                    );
+                    if let Err(err) = webview.evaluate_script(&js) {
+                        eprintln!("unable to update titlebar due to err: {}", err);
+                    }
+                    self.title = new_title.clone();
+                }
+                command.apply(window);
+            }
+        }
+        true
+    }
+
+    /// Evaluates `js` and replies with its JSON-serialized result.
+    fn evaluate_with_result(&self, js: &str, reply: Sender<String>) {
+        let callback = move |result: String| {
+            // luau might have stopped waiting already, so there's nobody to tell if this fails
+            let _ = reply.send(result);
+        };
+        if let Err(err) = self.webview.evaluate_script_with_callback(js, callback) {
+            eprintln!("unable to evaluate script due to err: {}", err);
+        }
+    }
+
+    /// Tells Luau the window closed.
+    fn report_closed(&self) {
+        // only fails once Luau has let go of the handle, and then there's nobody to tell
+        let _ = self.sender.send(ToLuau::WindowClosed);
+    }
+
+    /// Forwards native window events that Luau opted into with the `events` option.
+    ///
+    /// Returns false once the window should be closed.
+    pub fn handle_window_event(&self, event: WindowEvent<'_>) -> bool {
+        let events = self.options.events;
+        let message = match event {
+            WindowEvent::CloseRequested => {
+                self.report_closed();
+                return false;
+            },
+            WindowEvent::Resized(size) if events.contains(EventMask::RESIZED) => {
+                ToLuau::Resized(size.width as f32, size.height as f32)
+            },
+            WindowEvent::Moved(position) if events.contains(EventMask::MOVED) => {
+                ToLuau::Moved(position.x as f32, position.y as f32)
+            },
+            WindowEvent::Focused(focused) if events.contains(EventMask::FOCUSED) => {
+                ToLuau::Focused(focused)
+            },
+            WindowEvent::ScaleFactorChanged { scale_factor, new_inner_size } if events.contains(EventMask::SCALE_FACTOR_CHANGED) => {
+                ToLuau::ScaleFactorChanged(scale_factor, new_inner_size.width as f32, new_inner_size.height as f32)
+            },
+            WindowEvent::ThemeChanged(theme) if events.contains(EventMask::THEME_CHANGED) => {
+                ToLuau::ThemeChanged(match theme {
+                    Theme::Dark => "dark",
+                    _ => "light",
+                })
+            },
+            WindowEvent::DroppedFile(path) if events.contains(EventMask::FILE_DROPPED) => {
+                ToLuau::FileDropped(path)
+            },
+            WindowEvent::HoveredFile(path) if events.contains(EventMask::FILE_HOVERED) => {
+                ToLuau::FileHovered(path)
+            },
+            WindowEvent::HoveredFileCancelled if events.contains(EventMask::FILE_HOVERED) => {
+                ToLuau::FileHoverCancelled
+            },
+            _ => return true,
+        };
+        if let Err(err) = self.sender.send(message) {
+            eprintln!("unable to send window event due to err: {}", err);
+        }
+        true
+    }
+
+    /// Handles input from the page.
+    ///
+    /// Returns false once the window should be closed.
+    pub fn handle_user_event(&mut self, event: UserEvent) -> bool {
+        let window = &self.window;
+        match event {
+            UserEvent::Minimize => window.set_minimized(true),
+            UserEvent::Maximize => window.set_maximized(!window.is_maximized()),
+            UserEvent::DragWindow => {
+                // fails if the mouse button was already released, which isn't worth more than a note
+                if let Err(err) = window.drag_window() {
+                    eprintln!("unable to drag window due to err: {}", err);
+                }
+            },
+            UserEvent::MouseDown(x, y) => {
+                let res = resize::check_bounds(window.inner_size(), x, y, window.scale_factor());
+                match res {
+                    HitTestResult::Client | HitTestResult::NoWhere => {}
+                    _ => res.drag_resize_window(window),
+                }
+            }
+            UserEvent::MouseMove(x, y) => {
+                resize::check_bounds(window.inner_size(), x, y, window.scale_factor())
+                    .change_cursor(window);
+            }
+            UserEvent::CloseWindow => {
+                self.report_closed();
+                return false;
+            },
+            UserEvent::SendIpc(body) => {
+                let message = match self.options.ipc_mode {
+                    IpcMode::Json => match serde_json::from_str(&body) {
+                        Ok(value) => ToLuau::JsonMessage(value),
+                        Err(_) => ToLuau::IpcMessage(body),
+                    },
+                    IpcMode::String => ToLuau::IpcMessage(body),
+                };
+                if let Err(err) = self.sender.send(message) {
+                    eprintln!("unable to send ipc message due to err: {}", err);
+                }
+            }
+            UserEvent::Invoke(id, name, args) => {
+                if let Err(err) = self.sender.send(ToLuau::Invoke(id, name, args)) {
+                    eprintln!("unable to send seal.invoke call due to err: {}", err);
+                }
+            }
+            UserEvent::PageLoaded => {
+                if !self.loaded {
+                    self.loaded = true;
+                    for (js, reply) in std::mem::take(&mut self.pending_results) {
+                        self.evaluate_with_result(&js, reply);
+                    }
+                }
+            }
+        }
+        true
+    }
+}